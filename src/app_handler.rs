@@ -1,103 +1,400 @@
-use crate::{window::Window, RenderSettings, QUIT};
+use crate::{
+    events::CloseDecision,
+    gpu,
+    window::{CloseBehavior, PendingWindows, Window},
+    DeviceCapabilities, RedrawMode, RenderSettings, TaikaError, TaikaEvent,
+};
 use std::{
-    sync::{Arc, Mutex},
+    cell::RefCell,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     time::Instant,
 };
-use winit::{application::ApplicationHandler, event::WindowEvent};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ControlFlow, EventLoopProxy},
+};
 
-pub(crate) struct AppState<'a> {
+pub(crate) struct AppState<'a, T: Send + 'static> {
     pub device: Arc<Mutex<wgpu::Device>>,
     pub queue: Arc<Mutex<wgpu::Queue>>,
-    pub windows: Vec<Arc<Mutex<Window<'a>>>>,
+    pub windows: Vec<Arc<Mutex<Window<'a, T>>>>,
+    pub pending_windows: Rc<RefCell<PendingWindows<'a, T>>>,
+    pub main_window: Option<Arc<Mutex<Window<'a, T>>>>,
+    pub close_behavior: CloseBehavior,
     pub instance: wgpu::Instance,
     pub adapter: wgpu::Adapter,
+    pub capabilities: DeviceCapabilities,
+    /// Set by wgpu once the current device is lost
+    pub device_lost: Arc<AtomicBool>,
+    /// Used to wake the event loop once a recreated device is lost
+    pub proxy: EventLoopProxy<TaikaEvent<T>>,
     pub render_settings: RenderSettings,
+    /// The first fatal error that occurred, returned from [`crate::EventLoop::run`]
+    pub error: Option<TaikaError>,
+    pub shutting_down: bool,
+    /// True until the application is first resumed and while it is suspended. No surfaces
+    /// exist while suspended
+    pub suspended: bool,
 }
 
-impl<'a> ApplicationHandler<()> for AppState<'a> {
-    fn user_event(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop, _event: ()) {}
+impl<'a, T: Send + 'static> AppState<'a, T> {
+    /// Creates the winit window and surface of a window and initializes it against the device
+    fn init_window(
+        &self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window: &Arc<Mutex<Window<'a, T>>>,
+    ) -> Result<(), TaikaError> {
+        let mut window = window.lock().unwrap();
+        let win = event_loop.create_window(window.attributes.clone())?;
+        window.init(&self.instance, win)?;
+        window.reset_fixed_timestep(&self.render_settings);
+        window.configure_surface(
+            &self.adapter,
+            &self.device.lock().unwrap(),
+            &self.render_settings,
+        )?;
+        window.do_device_init(
+            &self.adapter,
+            self.device.clone(),
+            self.queue.clone(),
+            &self.capabilities,
+        );
+        window.request_redraw();
+        Ok(())
+    }
 
-    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        for window in &self.windows {
-            let window_attributes = winit::window::WindowAttributes::default()
-                .with_title(window.lock().unwrap().title.clone())
-                .with_min_inner_size(winit::dpi::LogicalSize::new(20.0, 20.0));
-            let win = event_loop.create_window(window_attributes).unwrap();
-            window.lock().unwrap().init(&self.instance, win).unwrap();
+    /// Recreates the surface of a window after the application has been resumed
+    fn resume_window(&self, window: &Arc<Mutex<Window<'a, T>>>) -> Result<(), TaikaError> {
+        let mut window = window.lock().unwrap();
+        window.create_surface(&self.instance)?;
+        window.configure_surface(
+            &self.adapter,
+            &self.device.lock().unwrap(),
+            &self.render_settings,
+        )?;
+        window.request_redraw();
+        Ok(())
+    }
+
+    /// Creates and destroys the windows queued by a [`crate::window::WindowManager`]
+    fn process_pending_windows(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.suspended {
+            // windows can't get surfaces while suspended, they're created once we're resumed
+            return;
+        }
+        let (created, destroyed) = {
+            let mut pending = self.pending_windows.borrow_mut();
+            (
+                std::mem::take(&mut pending.created),
+                std::mem::take(&mut pending.destroyed),
+            )
+        };
+        for window in created {
+            if let Err(error) = self.init_window(event_loop, &window) {
+                self.fail(event_loop, error);
+                return;
+            }
+            if self.main_window.is_none() {
+                self.main_window = Some(window.clone());
+            }
+            window.lock().unwrap().do_resumed();
+            self.windows.push(window);
         }
+        for window in destroyed {
+            if !self.windows.iter().any(|w| Arc::ptr_eq(w, &window)) {
+                continue;
+            }
+            // destroying a window can't be vetoed, the handler is only notified
+            window.lock().unwrap().do_closed();
+            self.window_closed(event_loop, &window);
+        }
+    }
+
+    /// Removes a window from the event loop and drops its surface. Returns false if the window
+    /// wasn't part of the event loop
+    fn remove_window(&mut self, window: &Arc<Mutex<Window<'a, T>>>) -> bool {
+        let Some(index) = self.windows.iter().position(|w| Arc::ptr_eq(w, window)) else {
+            return false;
+        };
+        self.windows.remove(index);
+        window.lock().unwrap().destroy();
+        true
+    }
+
+    /// Recreates the device if it was lost. The flag is checked again as the wake up event may
+    /// arrive after the device was already recreated
+    fn recover_if_lost(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if !self.shutting_down && self.device_lost.load(Ordering::SeqCst) {
+            self.recover_device(event_loop);
+        }
+    }
 
+    /// Recreates the adapter, device and queue after the device was lost and initializes every
+    /// window again. The device and queue are swapped in place, so the handles given out in
+    /// [`crate::events::EventHandler::device_init`] stay valid
+    fn recover_device(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        for window in &self.windows {
+            window.lock().unwrap().do_device_lost();
+        }
+        let result = gpu::block_on(async {
+            let adapter = gpu::request_adapter(&self.instance, &self.render_settings).await?;
+            let (device, queue, capabilities) =
+                gpu::request_device(&adapter, &self.render_settings).await?;
+            Ok::<_, TaikaError>((adapter, device, queue, capabilities))
+        });
+        let (adapter, device, queue, capabilities) = match result {
+            Ok(result) => result,
+            Err(error) => {
+                self.fail(event_loop, error);
+                return;
+            }
+        };
+        self.device_lost = gpu::watch_device_lost(&device, self.proxy.clone());
+        *self.device.lock().unwrap() = device;
+        *self.queue.lock().unwrap() = queue;
+        self.adapter = adapter;
+        self.capabilities = capabilities;
+        let mut error = None;
         for window in &self.windows {
-            window.lock().unwrap().configure_surface(
+            let mut window = window.lock().unwrap();
+            if !window.is_initialized() {
+                // will be initialized against the new device once resumed
+                continue;
+            }
+            if window.has_surface() {
+                if let Err(e) = window.configure_surface(
+                    &self.adapter,
+                    &self.device.lock().unwrap(),
+                    &self.render_settings,
+                ) {
+                    error = Some(e);
+                    break;
+                }
+                window.request_redraw();
+            }
+            window.do_device_init(
                 &self.adapter,
-                &self.device.lock().unwrap(),
-                &self.render_settings,
+                self.device.clone(),
+                self.queue.clone(),
+                &self.capabilities,
             );
         }
-        let windows = self.windows.clone();
-        let device = self.device.clone();
-        let queue = self.queue.clone();
-        for window in &windows {
-            window
-                .lock()
-                .unwrap()
-                .do_device_init(&self.adapter, device.clone(), queue.clone());
+        if let Some(error) = error {
+            self.fail(event_loop, error);
         }
+    }
+
+    /// Handles a close request from the user, following the [`CloseBehavior`]
+    fn close_requested(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window: &Arc<Mutex<Window<'a, T>>>,
+    ) {
+        if window.lock().unwrap().do_closed() == CloseDecision::Veto {
+            return;
+        }
+        self.window_closed(event_loop, window);
+    }
+
+    /// Removes a closed window and exits the event loop if the [`CloseBehavior`] says so
+    fn window_closed(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window: &Arc<Mutex<Window<'a, T>>>,
+    ) {
+        self.remove_window(window);
+        let is_main_window = self
+            .main_window
+            .as_ref()
+            .is_some_and(|main| Arc::ptr_eq(main, window));
+        let exit = match self.close_behavior {
+            CloseBehavior::ExitOnLastWindowClosed => self.windows.is_empty(),
+            CloseBehavior::ExitOnMainWindowClosed => is_main_window || self.windows.is_empty(),
+            CloseBehavior::ExitOnAnyWindowClosed => true,
+        };
+        if exit {
+            self.shutdown(event_loop);
+        }
+    }
+
+    fn fail(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, error: TaikaError) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+        self.shutdown(event_loop);
+    }
+
+    /// Closes every window, waits for the GPU to finish its work and releases the surfaces
+    /// before the device is dropped
+    fn shutdown(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.shutting_down {
+            return;
+        }
+        self.shutting_down = true;
         for window in &self.windows {
-            window.lock().unwrap().request_redraw();
+            let mut window = window.lock().unwrap();
+            if window.is_initialized() {
+                window.do_closed();
+            }
         }
+        let _ = self.device.lock().unwrap().poll(wgpu::PollType::Wait);
+        for window in self.windows.drain(..) {
+            window.lock().unwrap().destroy();
+        }
+        event_loop.exit();
     }
+}
 
-    fn window_event(
+impl<'a, T: Send + 'static> ApplicationHandler<TaikaEvent<T>> for AppState<'a, T> {
+    fn user_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        window_id: winit::window::WindowId,
-        event: winit::event::WindowEvent,
+        event: TaikaEvent<T>,
     ) {
-        if *QUIT.lock().unwrap() {
-            event_loop.exit();
-            for window in self.windows.iter() {
-                window.lock().unwrap().do_closed();
+        match event {
+            TaikaEvent::Quit => self.shutdown(event_loop),
+            TaikaEvent::WindowsChanged => self.process_pending_windows(event_loop),
+            TaikaEvent::Redraw(window_id) => {
+                for window in &self.windows {
+                    let window = window.lock().unwrap();
+                    if window.is_initialized()
+                        && window_id.is_none_or(|id| id == window.get_window_id())
+                    {
+                        window.request_redraw();
+                    }
+                }
             }
+            TaikaEvent::UpdateRenderSettings(update) => {
+                let fixed_update = (
+                    self.render_settings.fixed_update_rate,
+                    self.render_settings.max_fixed_updates_per_frame,
+                );
+                update.apply(&mut self.render_settings);
+                let fixed_update_changed = fixed_update
+                    != (
+                        self.render_settings.fixed_update_rate,
+                        self.render_settings.max_fixed_updates_per_frame,
+                    );
+                for window in &self.windows {
+                    let mut window = window.lock().unwrap();
+                    if fixed_update_changed {
+                        window.reset_fixed_timestep(&self.render_settings);
+                    }
+                    window.surface_outdated = true;
+                    window.frame_pacer.reset();
+                    window.request_redraw();
+                }
+            }
+            TaikaEvent::DeviceLost => self.recover_if_lost(event_loop),
+            TaikaEvent::User(event) => {
+                for window in &self.windows {
+                    window.lock().unwrap().do_user_event(&event);
+                }
+            }
+        }
+    }
+
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if !self.suspended {
+            return;
+        }
+        self.suspended = false;
+        for window in self.windows.clone() {
+            let initialized = window.lock().unwrap().is_initialized();
+            let result = if initialized {
+                // the window survived a suspend, it only needs a new surface
+                self.resume_window(&window)
+            } else {
+                self.init_window(event_loop, &window)
+            };
+            if let Err(error) = result {
+                self.fail(event_loop, error);
+                return;
+            }
+            window.lock().unwrap().do_resumed();
+        }
+        self.process_pending_windows(event_loop);
+    }
+
+    fn suspended(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.suspended {
             return;
         }
+        self.suspended = true;
+        for window in &self.windows {
+            let mut window = window.lock().unwrap();
+            window.do_suspended();
+            window.drop_surface();
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        window_id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
         for window in self.windows.iter() {
             if window.lock().unwrap().get_window_id() == window_id {
                 match event {
-                    WindowEvent::Resized(physical_size) => {
-                        window.lock().unwrap().resize_surface(
+                    WindowEvent::Resized(physical_size) if window.lock().unwrap().has_surface() => {
+                        let mut window = window.lock().unwrap();
+                        window.resize_surface(
                             &self.device.lock().unwrap(),
                             physical_size,
                             &self.queue.lock().unwrap(),
                         );
+                        window.request_redraw();
                     }
                     WindowEvent::CloseRequested => {
-                        window.lock().unwrap().do_closed();
-                        event_loop.exit();
+                        let window = window.clone();
+                        self.close_requested(event_loop, &window);
+                        return;
                     }
                     WindowEvent::Focused(focused) => {
                         window.lock().unwrap().do_focus(focused);
                     }
-                    WindowEvent::RedrawRequested => {
+                    WindowEvent::RedrawRequested => 'frame: {
                         let mut window = window.lock().unwrap();
-                        if let Some(max_framerate) = self.render_settings.max_framerate {
-                            if window.last_frame.elapsed().as_secs_f64()
-                                < 1.0 / max_framerate as f64
-                                && !self.render_settings.vsync
-                            {
-                                window.request_redraw();
-                                break;
-                            }
+                        if !window.has_surface() {
+                            break 'frame;
                         }
-                        window.last_frame = Instant::now();
-                        window.do_frame();
-                        let surface = window.get_surface();
-                        let frame = surface.get_current_texture();
-                        if let Err(err) = frame {
-                            println!("Failed to get current frame. Window state listed below:");
-                            println!("Error: {:?}", err);
-                            return;
+                        if window.surface_outdated {
+                            let result = window.configure_surface(
+                                &self.adapter,
+                                &self.device.lock().unwrap(),
+                                &self.render_settings,
+                            );
+                            if let Err(error) = result {
+                                drop(window);
+                                self.fail(event_loop, error);
+                                return;
+                            }
                         }
-                        let frame = frame.unwrap();
+                        // The frame is acquired before the handler is told about it, so skipped
+                        // frames don't start a frame that never ends
+                        let frame = window.acquire_frame(
+                            &self.device.lock().unwrap(),
+                            &self.queue.lock().unwrap(),
+                        );
+                        let frame = match frame {
+                            Ok(Some(frame)) => frame,
+                            Ok(None) => {
+                                window.request_redraw();
+                                break 'frame;
+                            }
+                            Err(error) => {
+                                drop(window);
+                                self.fail(event_loop, error.into());
+                                return;
+                            }
+                        };
+                        let frame_info = window.do_frame();
                         let view = frame.texture.create_view(&wgpu::TextureViewDescriptor {
                             format: Some(window.get_target_properties().view_format),
                             ..Default::default()
@@ -113,12 +410,25 @@ impl<'a> ApplicationHandler<()> for AppState<'a> {
                             &self.queue.lock().unwrap(),
                             &view,
                             window.get_target_properties(),
+                            &frame_info,
                         );
                         self.queue.lock().unwrap().submit(Some(encoder.finish()));
                         window.pre_present_notify();
                         frame.present();
-                        window.do_after_frame();
-                        window.request_redraw();
+                        window.do_after_frame(&frame_info);
+                        match (
+                            self.render_settings.redraw_mode,
+                            window.frame_interval(&self.render_settings),
+                        ) {
+                            (RedrawMode::OnDemand, _) => window.frame_pacer.reset(),
+                            (RedrawMode::Continuous, Some(interval)) => {
+                                window.frame_pacer.schedule(interval)
+                            }
+                            (RedrawMode::Continuous, None) => {
+                                window.frame_pacer.reset();
+                                window.request_redraw();
+                            }
+                        }
                     }
                     _ => {}
                 }
@@ -136,5 +446,21 @@ impl<'a> ApplicationHandler<()> for AppState<'a> {
     ) {
     }
 
-    fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {}
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        self.recover_if_lost(event_loop);
+        // Sleep until the earliest paced frame is almost due
+        let mut wake_time = None;
+        for window in &self.windows {
+            let mut window = window.lock().unwrap();
+            if window.frame_pacer.wait_if_due() {
+                window.request_redraw();
+            } else if let Some(time) = window.frame_pacer.wake_time() {
+                wake_time = Some(wake_time.map_or(time, |t: Instant| t.min(time)));
+            }
+        }
+        event_loop.set_control_flow(match wake_time {
+            Some(time) => ControlFlow::WaitUntil(time),
+            None => ControlFlow::Wait,
+        });
+    }
 }
@@ -1,6 +1,8 @@
+use crate::TaikaError;
+
 /// An asset that can be resolved into binary data
 pub trait ResolvableAsset {
-    fn resolve(&self) -> Vec<u8>;
+    fn resolve(&self) -> Result<Vec<u8>, TaikaError>;
     fn get_name(&self) -> &str;
 }
 
@@ -19,11 +21,15 @@ impl DiskAsset {
 }
 
 impl ResolvableAsset for DiskAsset {
-    fn resolve(&self) -> Vec<u8> {
-        let path = std::env::current_exe().unwrap();
-        let path = path.parent().unwrap();
+    fn resolve(&self) -> Result<Vec<u8>, TaikaError> {
+        let error = |source| TaikaError::Asset {
+            name: self.path.clone(),
+            source,
+        };
+        let path = std::env::current_exe().map_err(error)?;
+        let path = path.parent().unwrap_or(&path);
         let path = path.join(&self.path);
-        std::fs::read(path).unwrap()
+        std::fs::read(path).map_err(error)
     }
 
     fn get_name(&self) -> &str {
@@ -48,8 +54,8 @@ impl MemoryAsset {
 }
 
 impl ResolvableAsset for MemoryAsset {
-    fn resolve(&self) -> Vec<u8> {
-        self.data.clone()
+    fn resolve(&self) -> Result<Vec<u8>, TaikaError> {
+        Ok(self.data.clone())
     }
 
     fn get_name(&self) -> &str {
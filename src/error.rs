@@ -0,0 +1,136 @@
+use std::fmt;
+
+/// Errors that can occur in taika
+#[derive(Debug)]
+pub enum TaikaError {
+    /// No suitable adapter was found
+    AdapterRequest(wgpu::RequestAdapterError),
+    /// The adapter failed to create a device
+    DeviceRequest(wgpu::RequestDeviceError),
+    /// A surface could not be created for a window
+    SurfaceCreation(wgpu::CreateSurfaceError),
+    /// The next frame could not be acquired from a surface
+    SurfaceAcquire(wgpu::SurfaceError),
+    /// The adapter can't present to the surface of a window, for example because a different
+    /// adapter was forced with [`crate::RenderSettings::adapter_selector`]
+    SurfaceUnsupported { adapter: String },
+    /// The OS failed to create a window
+    WindowCreation(winit::error::OsError),
+    /// The event loop could not be created or failed while running
+    EventLoop(winit::error::EventLoopError),
+    /// A buffer could not be read back from the GPU
+    Readback(wgpu::BufferAsyncError),
+    /// An asset could not be read
+    Asset {
+        name: String,
+        source: std::io::Error,
+    },
+    /// An asset was read but its contents were invalid, for example a shader that isn't valid UTF-8
+    InvalidAsset { name: String, reason: String },
+    /// A render graph pass reads a resource that no pass writes
+    RenderGraphMissingInput { pass: String, resource: String },
+    /// The passes of a render graph depend on each other in a cycle
+    RenderGraphCycle { passes: Vec<String> },
+}
+
+impl fmt::Display for TaikaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaikaError::AdapterRequest(e) => write!(f, "No suitable adapter found: {}", e),
+            TaikaError::DeviceRequest(e) => write!(f, "Failed to create device: {}", e),
+            TaikaError::SurfaceCreation(e) => write!(f, "Failed to create surface: {}", e),
+            TaikaError::SurfaceAcquire(e) => write!(f, "Failed to acquire frame: {}", e),
+            TaikaError::SurfaceUnsupported { adapter } => {
+                write!(
+                    f,
+                    "Adapter '{}' can't present to the window surface",
+                    adapter
+                )
+            }
+            TaikaError::WindowCreation(e) => write!(f, "Failed to create window: {}", e),
+            TaikaError::EventLoop(e) => write!(f, "Event loop error: {}", e),
+            TaikaError::Readback(e) => write!(f, "Failed to read back buffer: {}", e),
+            TaikaError::Asset { name, source } => {
+                write!(f, "Failed to read asset '{}': {}", name, source)
+            }
+            TaikaError::InvalidAsset { name, reason } => {
+                write!(f, "Invalid asset '{}': {}", name, reason)
+            }
+            TaikaError::RenderGraphMissingInput { pass, resource } => {
+                write!(
+                    f,
+                    "Render pass '{}' reads '{}', which no pass writes",
+                    pass, resource
+                )
+            }
+            TaikaError::RenderGraphCycle { passes } => {
+                write!(
+                    f,
+                    "Render graph contains a cycle between the passes {}",
+                    passes.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TaikaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TaikaError::AdapterRequest(e) => Some(e),
+            TaikaError::DeviceRequest(e) => Some(e),
+            TaikaError::SurfaceCreation(e) => Some(e),
+            TaikaError::SurfaceAcquire(e) => Some(e),
+            TaikaError::SurfaceUnsupported { .. } => None,
+            TaikaError::WindowCreation(e) => Some(e),
+            TaikaError::EventLoop(e) => Some(e),
+            TaikaError::Readback(e) => Some(e),
+            TaikaError::Asset { source, .. } => Some(source),
+            TaikaError::InvalidAsset { .. } => None,
+            TaikaError::RenderGraphMissingInput { .. } => None,
+            TaikaError::RenderGraphCycle { .. } => None,
+        }
+    }
+}
+
+impl From<wgpu::RequestAdapterError> for TaikaError {
+    fn from(e: wgpu::RequestAdapterError) -> Self {
+        TaikaError::AdapterRequest(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for TaikaError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        TaikaError::DeviceRequest(e)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for TaikaError {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        TaikaError::SurfaceCreation(e)
+    }
+}
+
+impl From<wgpu::SurfaceError> for TaikaError {
+    fn from(e: wgpu::SurfaceError) -> Self {
+        TaikaError::SurfaceAcquire(e)
+    }
+}
+
+impl From<winit::error::OsError> for TaikaError {
+    fn from(e: winit::error::OsError) -> Self {
+        TaikaError::WindowCreation(e)
+    }
+}
+
+impl From<winit::error::EventLoopError> for TaikaError {
+    fn from(e: winit::error::EventLoopError) -> Self {
+        TaikaError::EventLoop(e)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for TaikaError {
+    fn from(e: wgpu::BufferAsyncError) -> Self {
+        TaikaError::Readback(e)
+    }
+}
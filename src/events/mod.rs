@@ -1,11 +1,40 @@
 use async_trait::async_trait;
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use crate::window::TargetProperties;
+use crate::{timing::FrameInfo, window::TargetProperties, DeviceCapabilities};
 
+/// Returned from [`EventHandler::window_close`] to decide whether a window should close
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseDecision {
+    /// Close the window
+    Close,
+    /// Keep the window open
+    Veto,
+}
+
+/// What taika did after failing to acquire the next frame of a window's surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceRecovery {
+    /// The surface was reconfigured and the frame was acquired successfully
+    Reconfigured,
+    /// The frame was skipped, taika will try again on the next frame
+    Skipped,
+    /// The error can't be recovered from, the event loop will exit with
+    /// [`crate::TaikaError::SurfaceAcquire`]
+    Fatal,
+}
+
+/// Receives the events of a single window. `T` is the type of user events of the
+/// [`crate::EventLoop`]
 #[async_trait]
-pub trait EventHandler {
-    fn window_close(&mut self);
+pub trait EventHandler<T = ()> {
+    /// Called when the window is about to close. Returning [`CloseDecision::Veto`] keeps the
+    /// window open if the user requested the close. The decision is ignored when the event loop
+    /// is shutting down or the window is destroyed by a [`crate::window::WindowManager`]
+    fn window_close(&mut self) -> CloseDecision;
     fn window_resize(
         &mut self,
         width: u32,
@@ -15,14 +44,33 @@ pub trait EventHandler {
     );
     fn window_focus(&mut self);
     fn window_unfocus(&mut self);
-    fn window_frame(&mut self);
-    fn window_after_frame(&mut self);
+    fn window_frame(&mut self, frame_info: &FrameInfo);
+    /// Called at a fixed rate set by [`crate::RenderSettings::fixed_update_rate`], before
+    /// [`EventHandler::window_frame`]. Might be called zero or multiple times per frame
+    fn fixed_update(&mut self, _delta: Duration) {}
+    fn window_after_frame(&mut self, frame_info: &FrameInfo);
+    /// Called when the GPU device was lost. Taika recreates the device and calls
+    /// [`EventHandler::device_init`] again, all GPU resources created with the old device have to
+    /// be recreated there
+    fn device_lost(&mut self) {}
     fn device_init(
         &mut self,
         adapter: &wgpu::Adapter,
         device: Arc<Mutex<wgpu::Device>>,
         queue: Arc<Mutex<wgpu::Queue>>,
         target_properties: TargetProperties,
+        capabilities: DeviceCapabilities,
     );
     fn window_event(&mut self, event: &winit::event::WindowEvent);
+    /// Called when the application is suspended (for example when it is sent to the background
+    /// on mobile). The window's surface is dropped until the application is resumed
+    fn suspended(&mut self) {}
+    /// Called when the application is resumed and the window has a surface again. Also called
+    /// once on startup after [`EventHandler::device_init`]
+    fn resumed(&mut self) {}
+    /// Called when the next frame couldn't be acquired from the window's surface
+    fn surface_error(&mut self, _error: &wgpu::SurfaceError, _recovery: SurfaceRecovery) {}
+    /// Called on the main thread when a user event is posted with
+    /// [`crate::LoopHandle::send_event`]
+    fn user_event(&mut self, _event: &T) {}
 }
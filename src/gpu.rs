@@ -0,0 +1,207 @@
+use std::{
+    future::Future,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use winit::event_loop::EventLoopProxy;
+
+use crate::{DeviceCapabilities, RenderSettings, TaikaError, TaikaEvent};
+
+pub(crate) fn create_instance(render_settings: &RenderSettings) -> wgpu::Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: render_settings.backends,
+        ..Default::default()
+    })
+}
+
+/// Requests an adapter, letting the [`crate::AdapterSelector`] pick one if it has been set
+pub(crate) async fn request_adapter(
+    instance: &wgpu::Instance,
+    render_settings: &RenderSettings,
+) -> Result<wgpu::Adapter, TaikaError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(selector) = &render_settings.adapter_selector {
+        let mut adapters = instance.enumerate_adapters(render_settings.backends);
+        let infos: Vec<wgpu::AdapterInfo> = adapters.iter().map(|a| a.get_info()).collect();
+        if let Some(index) = selector.select(&infos) {
+            if index < adapters.len() {
+                return Ok(adapters.swap_remove(index));
+            }
+        }
+    }
+    Ok(instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: render_settings.power_preference,
+            force_fallback_adapter: render_settings.force_fallback_adapter,
+            compatible_surface: None,
+        })
+        .await?)
+}
+
+/// Requests a device with the required features and limits, plus every optional feature and
+/// limit the adapter supports
+pub(crate) async fn request_device(
+    adapter: &wgpu::Adapter,
+    render_settings: &RenderSettings,
+) -> Result<(wgpu::Device, wgpu::Queue, DeviceCapabilities), TaikaError> {
+    let mut required_limits = match &render_settings.required_limits {
+        Some(limits) => limits.clone(),
+        None => wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+    };
+    if let Some(optional_limits) = &render_settings.optional_limits {
+        required_limits = negotiate_limits(required_limits, optional_limits, &adapter.limits());
+    }
+    let features = render_settings.required_features
+        | (render_settings.optional_features & adapter.features());
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            required_features: features,
+            required_limits,
+            memory_hints: wgpu::MemoryHints::Performance,
+            trace: wgpu::Trace::Off,
+        })
+        .await?;
+    let capabilities = DeviceCapabilities {
+        features: device.features(),
+        limits: device.limits(),
+    };
+    Ok((device, queue, capabilities))
+}
+
+/// Raises the required limits towards the optional limits, as far as the adapter allows.
+/// Required limits are never lowered.
+fn negotiate_limits(
+    required: wgpu::Limits,
+    optional: &wgpu::Limits,
+    adapter: &wgpu::Limits,
+) -> wgpu::Limits {
+    let mut limits = required;
+    macro_rules! maximum {
+        ($($name:ident),*) => {
+            $(limits.$name = limits.$name.max(optional.$name.min(adapter.$name));)*
+        };
+    }
+    macro_rules! minimum {
+        ($($name:ident),*) => {
+            $(limits.$name = limits.$name.min(optional.$name.max(adapter.$name));)*
+        };
+    }
+    maximum!(
+        max_texture_dimension_1d,
+        max_texture_dimension_2d,
+        max_texture_dimension_3d,
+        max_texture_array_layers,
+        max_bind_groups,
+        max_bindings_per_bind_group,
+        max_dynamic_uniform_buffers_per_pipeline_layout,
+        max_dynamic_storage_buffers_per_pipeline_layout,
+        max_sampled_textures_per_shader_stage,
+        max_samplers_per_shader_stage,
+        max_storage_buffers_per_shader_stage,
+        max_storage_textures_per_shader_stage,
+        max_uniform_buffers_per_shader_stage,
+        max_binding_array_elements_per_shader_stage,
+        max_binding_array_sampler_elements_per_shader_stage,
+        max_uniform_buffer_binding_size,
+        max_storage_buffer_binding_size,
+        max_vertex_buffers,
+        max_buffer_size,
+        max_vertex_attributes,
+        max_vertex_buffer_array_stride,
+        max_inter_stage_shader_components,
+        max_color_attachments,
+        max_color_attachment_bytes_per_sample,
+        max_compute_workgroup_storage_size,
+        max_compute_invocations_per_workgroup,
+        max_compute_workgroup_size_x,
+        max_compute_workgroup_size_y,
+        max_compute_workgroup_size_z,
+        max_compute_workgroups_per_dimension,
+        max_push_constant_size,
+        max_non_sampler_bindings
+    );
+    minimum!(
+        min_uniform_buffer_offset_alignment,
+        min_storage_buffer_offset_alignment
+    );
+    limits
+}
+
+/// Returns the highest sample count up to `requested` that the device supports for rendering to
+/// `format`. wgpu only allows the adapter specific format features if the device was created with
+/// `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`, otherwise the guaranteed features are used. Without
+/// an adapter the guaranteed features are used as well, which is always valid but might be lower
+/// than the adapter supports.
+///
+/// Multisampled textures are resolved into a texture of `format`, so formats that can't be
+/// resolved into are rendered without MSAA
+pub(crate) fn select_sample_count(
+    adapter: Option<&wgpu::Adapter>,
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    let features = device.features();
+    let flags = match adapter {
+        Some(adapter)
+            if features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) =>
+        {
+            adapter.get_texture_format_features(format).flags
+        }
+        _ => format.guaranteed_format_features(features).flags,
+    };
+    if !flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE) {
+        return 1;
+    }
+    [16, 8, 4, 2]
+        .into_iter()
+        .find(|&count| count <= requested && flags.sample_count_supported(count))
+        .unwrap_or(1)
+}
+
+/// Returns a flag that is set once the device is lost, the event loop is woken up with
+/// [`TaikaEvent::DeviceLost`] at the same time. Explicitly destroying the device doesn't set the
+/// flag
+pub(crate) fn watch_device_lost<T: Send + 'static>(
+    device: &wgpu::Device,
+    proxy: EventLoopProxy<TaikaEvent<T>>,
+) -> Arc<AtomicBool> {
+    let lost = Arc::new(AtomicBool::new(false));
+    let flag = lost.clone();
+    device.set_device_lost_callback(move |reason, _message| {
+        if reason != wgpu::DeviceLostReason::Destroyed {
+            flag.store(true, Ordering::SeqCst);
+            let _ = proxy.send_event(TaikaEvent::DeviceLost);
+        }
+    });
+    lost
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Drives a future to completion on the current thread. Used where wgpu futures have to be
+/// awaited from inside the (synchronous) event loop
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
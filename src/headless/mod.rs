@@ -0,0 +1,234 @@
+//! Offscreen rendering without any windows or display server.
+//!
+//! The [`HeadlessRenderer`] creates its own wgpu device and renders a [`RenderPipeline`] into a
+//! texture, which is then read back into CPU memory. This is mainly useful for testing render
+//! passes and drawables on machines without a display (or even without a GPU, by using the
+//! fallback adapter).
+use std::sync::{mpsc, Arc, Mutex};
+
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    gpu, rendering::RenderPipeline, timing::FrameClock, window::TargetProperties,
+    DeviceCapabilities, RenderSettings, TaikaError,
+};
+
+/// Settings for a [`HeadlessRenderer`]
+#[derive(Debug, Clone)]
+pub struct HeadlessSettings {
+    /// Width of the render target in pixels
+    pub width: u32,
+    /// Height of the render target in pixels
+    pub height: u32,
+    /// Format of the render target
+    pub format: wgpu::TextureFormat,
+    /// Settings used when creating the device
+    pub render_settings: RenderSettings,
+}
+
+impl Default for HeadlessSettings {
+    fn default() -> Self {
+        Self {
+            width: 256,
+            height: 256,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            render_settings: RenderSettings::default(),
+        }
+    }
+}
+
+/// A single frame rendered by a [`HeadlessRenderer`]
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    /// The raw pixel data of the frame, rows are tightly packed
+    pub data: Vec<u8>,
+}
+
+/// Renders a [`RenderPipeline`] into an offscreen texture
+pub struct HeadlessRenderer {
+    device: Arc<Mutex<wgpu::Device>>,
+    queue: Arc<Mutex<wgpu::Queue>>,
+    adapter: wgpu::Adapter,
+    capabilities: DeviceCapabilities,
+    texture: wgpu::Texture,
+    readback_buffer: wgpu::Buffer,
+    padded_bytes_per_row: u32,
+    unpadded_bytes_per_row: u32,
+    render_pipeline: Arc<Mutex<dyn RenderPipeline>>,
+    target_properties: TargetProperties,
+    frame_clock: FrameClock,
+}
+
+impl HeadlessRenderer {
+    /// Creates the device and the offscreen target. If no adapter matching the render settings
+    /// is available the fallback (software) adapter will be used.
+    pub async fn new(
+        settings: HeadlessSettings,
+        pipeline: Arc<Mutex<dyn RenderPipeline>>,
+    ) -> Result<Self, TaikaError> {
+        let instance = gpu::create_instance(&settings.render_settings);
+        let adapter = match gpu::request_adapter(&instance, &settings.render_settings).await {
+            Ok(adapter) => adapter,
+            Err(_) if !settings.render_settings.force_fallback_adapter => {
+                let render_settings = RenderSettings {
+                    force_fallback_adapter: true,
+                    ..settings.render_settings.clone()
+                };
+                gpu::request_adapter(&instance, &render_settings).await?
+            }
+            Err(e) => return Err(e),
+        };
+        let (device, queue, capabilities) =
+            gpu::request_device(&adapter, &settings.render_settings).await?;
+
+        let width = settings.width.max(1);
+        let height = settings.height.max(1);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Taika headless target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: settings.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let bytes_per_pixel = settings.format.block_copy_size(None).unwrap_or(4);
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Taika headless readback"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let sample_count = gpu::select_sample_count(
+            Some(&adapter),
+            &device,
+            settings.format,
+            settings.render_settings.sample_count,
+        );
+        Ok(HeadlessRenderer {
+            device: Arc::new(Mutex::new(device)),
+            queue: Arc::new(Mutex::new(queue)),
+            adapter,
+            capabilities,
+            texture,
+            readback_buffer,
+            padded_bytes_per_row,
+            unpadded_bytes_per_row,
+            render_pipeline: pipeline,
+            target_properties: TargetProperties {
+                format: settings.format,
+                view_format: settings.format,
+                alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+                sample_count,
+                depth_format: None,
+                size: PhysicalSize::new(width, height),
+            },
+            frame_clock: FrameClock::new(),
+        })
+    }
+
+    /// Returns the device used for rendering
+    pub fn get_device(&self) -> Arc<Mutex<wgpu::Device>> {
+        self.device.clone()
+    }
+
+    /// Returns the queue used for rendering
+    pub fn get_queue(&self) -> Arc<Mutex<wgpu::Queue>> {
+        self.queue.clone()
+    }
+
+    /// Returns the adapter the device was created from
+    pub fn get_adapter(&self) -> &wgpu::Adapter {
+        &self.adapter
+    }
+
+    /// Returns the features and limits the device was created with
+    pub fn get_capabilities(&self) -> &DeviceCapabilities {
+        &self.capabilities
+    }
+
+    /// Returns the `TargetProperties` of the offscreen target
+    pub fn get_target_properties(&self) -> &TargetProperties {
+        &self.target_properties
+    }
+
+    /// Renders a single frame and reads it back
+    pub fn render_frame(&mut self) -> Result<Frame, TaikaError> {
+        let device = self.device.lock().unwrap();
+        let queue = self.queue.lock().unwrap();
+        let view = self.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.target_properties.view_format),
+            ..Default::default()
+        });
+        let frame_info = self.frame_clock.tick(PhysicalSize::new(
+            self.texture.width(),
+            self.texture.height(),
+        ));
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.render_pipeline.lock().unwrap().render(
+            &device,
+            &mut encoder,
+            &queue,
+            &view,
+            &self.target_properties,
+            &frame_info,
+        );
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.readback_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            self.texture.size(),
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = self.readback_buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        let _ = device.poll(wgpu::PollType::Wait);
+        receiver
+            .recv()
+            .expect("Readback callback was never called")?;
+
+        let mut data =
+            Vec::with_capacity((self.unpadded_bytes_per_row * self.texture.height()) as usize);
+        {
+            let mapped = slice.get_mapped_range();
+            for row in mapped.chunks(self.padded_bytes_per_row as usize) {
+                data.extend_from_slice(&row[..self.unpadded_bytes_per_row as usize]);
+            }
+        }
+        self.readback_buffer.unmap();
+        Ok(Frame {
+            width: self.texture.width(),
+            height: self.texture.height(),
+            format: self.texture.format(),
+            data,
+        })
+    }
+
+    /// Renders `count` frames, returning all of them in order
+    pub fn render_frames(&mut self, count: usize) -> Result<Vec<Frame>, TaikaError> {
+        (0..count).map(|_| self.render_frame()).collect()
+    }
+}
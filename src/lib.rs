@@ -30,8 +30,14 @@
 //!
 //!
 //! # Getting Started
-//! Use the [`EventLoop`] struct to get started
-use std::sync::{Arc, Mutex};
+//! Use the [`EventLoop`] struct to get started. For rendering without any windows (for example in
+//! CI) see [`headless::HeadlessRenderer`]
+use std::{
+    cell::RefCell,
+    fmt,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 pub use wgpu;
 pub use winit;
 
@@ -39,110 +45,241 @@ use winit::event_loop::ControlFlow;
 
 mod app_handler;
 pub mod asset_management;
+mod error;
 pub mod events;
+mod gpu;
+pub mod headless;
+mod loop_handle;
 pub mod math;
 pub mod rendering;
+pub mod timing;
 pub mod window;
 
-static QUIT: Mutex<bool> = Mutex::new(false);
+pub use error::TaikaError;
+pub use loop_handle::{LoopHandle, RenderSettingsUpdate, TaikaEvent};
 
 /// Settings for the renderer
 #[derive(Debug, Clone)]
 pub struct RenderSettings {
-    /// Whether or not to enable vsync. VSYNC on might lead to some latency
+    /// Whether or not to enable vsync. VSYNC on might lead to some latency. Only used if
+    /// `present_modes` is empty
     pub vsync: bool,
-    /// Features for wgpu
+    /// Preferred present modes, in order of preference. The first mode supported by the surface
+    /// is used, falling back to `Fifo` which is always supported. If empty the present mode is
+    /// picked based on `vsync`
+    pub present_modes: Vec<wgpu::PresentMode>,
+    /// The maximum number of frames the GPU is allowed to queue up. Lower values reduce latency,
+    /// higher values might improve throughput
+    pub desired_maximum_frame_latency: u32,
+    /// Preferred surface formats, in order of preference. An sRGB format is also accepted if the
+    /// surface only supports its linear variant, in which case the surface is rendered to through
+    /// an sRGB view. If empty or if none are supported the first format of the surface is used,
+    /// viewed as sRGB
+    pub surface_formats: Vec<wgpu::TextureFormat>,
+    /// Preferred composite alpha modes, in order of preference. If empty or if none are
+    /// supported, transparent windows prefer `PreMultiplied` and `PostMultiplied` and other
+    /// windows use the first mode of the surface
+    pub alpha_modes: Vec<wgpu::CompositeAlphaMode>,
+    /// Number of samples used for multisample anti-aliasing, 1 disables MSAA. If the adapter
+    /// doesn't support the sample count for the surface format the highest supported count below
+    /// it is used, see [`window::TargetProperties::sample_count`]
+    pub sample_count: u32,
+    /// Features for wgpu. Creating the device fails if the adapter doesn't support all of them
     pub required_features: wgpu::Features,
-    /// Maximun allowed framerate, only applies is `vsync` is false
+    /// Features that are enabled only if the adapter supports them. Use
+    /// [`DeviceCapabilities`] to check which features actually got enabled
+    pub optional_features: wgpu::Features,
+    /// Maximun allowed framerate, only applies if the present mode doesn't wait for vsync. The
+    /// event loop sleeps between frames instead of busy-waiting
     pub max_framerate: Option<u16>,
+    /// Whether windows are redrawn continuously or only when requested
+    pub redraw_mode: RedrawMode,
+    /// Rate of [`events::EventHandler::fixed_update`] in updates per second. `None` disables
+    /// fixed updates
+    pub fixed_update_rate: Option<u32>,
+    /// Maximum number of fixed updates run per frame. If a frame takes longer than this many
+    /// updates the remaining time is skipped, which prevents the simulation from falling further
+    /// and further behind
+    pub max_fixed_updates_per_frame: u32,
+    /// Which graphics backends wgpu is allowed to use, for example `wgpu::Backends::VULKAN` to
+    /// force vulkan on systems with broken drivers for other backends
+    pub backends: wgpu::Backends,
+    /// Power preference used when requesting an adapter
+    pub power_preference: wgpu::PowerPreference,
+    /// Forces the fallback (software) adapter to be used
+    pub force_fallback_adapter: bool,
+    /// Limits for wgpu. If `None` the downlevel defaults with the resolution limits of the
+    /// adapter are used
+    pub required_limits: Option<wgpu::Limits>,
+    /// Limits that are raised as far as the adapter allows, but never below the required limits
+    pub optional_limits: Option<wgpu::Limits>,
+    /// Optional callback used to pick an adapter from all available adapters. If the callback
+    /// returns `None` taika picks an adapter based on the other settings
+    pub adapter_selector: Option<AdapterSelector>,
 }
 
 impl Default for RenderSettings {
     fn default() -> Self {
         Self {
             vsync: true,
+            present_modes: Vec::new(),
+            desired_maximum_frame_latency: 1,
+            surface_formats: Vec::new(),
+            alpha_modes: Vec::new(),
+            sample_count: 1,
             required_features: wgpu::Features::empty(),
+            optional_features: wgpu::Features::empty(),
             max_framerate: None,
+            redraw_mode: RedrawMode::Continuous,
+            fixed_update_rate: None,
+            max_fixed_updates_per_frame: 5,
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            required_limits: None,
+            optional_limits: None,
+            adapter_selector: None,
         }
     }
 }
 
-/// Request the event loop to quit, closing all windows
-pub fn request_quit() {
-    let mut quit = QUIT.lock().unwrap();
-    *quit = true;
+type AdapterSelectorFn = dyn Fn(&[wgpu::AdapterInfo]) -> Option<usize> + Send + Sync;
+
+/// Decides when windows are redrawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedrawMode {
+    /// A new frame is rendered as soon as the previous one is done (or when the framerate limit
+    /// allows it)
+    Continuous,
+    /// Frames are only rendered when requested with [`window::Window::request_redraw`] or
+    /// [`LoopHandle::request_redraw`], or when the window is resized or exposed. Useful for tools
+    /// and menus that don't need to use the GPU while idle
+    OnDemand,
+}
+
+/// Picks an adapter by returning its index in the list of available adapters
+#[derive(Clone)]
+pub struct AdapterSelector(Arc<AdapterSelectorFn>);
+
+impl AdapterSelector {
+    pub fn new(
+        selector: impl Fn(&[wgpu::AdapterInfo]) -> Option<usize> + Send + Sync + 'static,
+    ) -> Self {
+        AdapterSelector(Arc::new(selector))
+    }
+
+    pub(crate) fn select(&self, adapters: &[wgpu::AdapterInfo]) -> Option<usize> {
+        (self.0)(adapters)
+    }
+}
+
+impl fmt::Debug for AdapterSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AdapterSelector")
+    }
+}
+
+/// The features and limits the device was actually created with
+#[derive(Debug, Clone)]
+pub struct DeviceCapabilities {
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+}
+
+impl DeviceCapabilities {
+    /// Returns true if all of the given features are enabled on the device
+    pub fn has_features(&self, features: wgpu::Features) -> bool {
+        self.features.contains(features)
+    }
 }
 
 /// Used to create windows and run the main loop of the application.
-pub struct EventLoop<'a> {
-    handle: winit::event_loop::EventLoop<()>,
-    windows: Vec<Arc<Mutex<window::Window<'a>>>>,
+///
+/// `T` is the type of user events that can be posted to the loop with [`LoopHandle::send_event`].
+/// It has to be `Send`, as wgpu might wake the loop from other threads when the device is lost
+pub struct EventLoop<'a, T: Send + 'static = ()> {
+    handle: winit::event_loop::EventLoop<TaikaEvent<T>>,
+    windows: Vec<Arc<Mutex<window::Window<'a, T>>>>,
+    pending_windows: Rc<RefCell<window::PendingWindows<'a, T>>>,
+    close_behavior: window::CloseBehavior,
     pub(crate) render_settings: RenderSettings,
 }
 
-impl<'a> EventLoop<'a> {
+impl<'a, T: Send + 'static> EventLoop<'a, T> {
     /// Initializes a new taika event loop.
     /// The event loop is used to create windows and run the main loop of the application.
-    pub fn new(
-        render_settings: RenderSettings,
-    ) -> Result<EventLoop<'a>, winit::error::EventLoopError> {
-        let event_loop = winit::event_loop::EventLoop::new()?;
+    pub fn new(render_settings: RenderSettings) -> Result<EventLoop<'a, T>, TaikaError> {
+        let event_loop = winit::event_loop::EventLoop::with_user_event().build()?;
         event_loop.set_control_flow(ControlFlow::Wait);
         Ok(EventLoop {
             handle: event_loop,
             windows: Vec::new(),
+            pending_windows: Rc::new(RefCell::new(window::PendingWindows {
+                created: Vec::new(),
+                destroyed: Vec::new(),
+            })),
+            close_behavior: window::CloseBehavior::default(),
             render_settings,
         })
     }
 
     /// Returns the underlying winit event loop
-    pub fn get_event_loop(&self) -> &winit::event_loop::EventLoop<()> {
+    pub fn get_event_loop(&self) -> &winit::event_loop::EventLoop<TaikaEvent<T>> {
         &self.handle
     }
 
+    /// Returns a [`LoopHandle`] that can be used to control the event loop from any thread
+    pub fn get_loop_handle(&self) -> LoopHandle<T> {
+        LoopHandle::new(self.handle.create_proxy())
+    }
+
+    /// Sets when closing a window exits the event loop. Defaults to
+    /// [`window::CloseBehavior::ExitOnLastWindowClosed`]
+    pub fn set_close_behavior(&mut self, close_behavior: window::CloseBehavior) {
+        self.close_behavior = close_behavior;
+    }
+
+    /// Returns a [`window::WindowManager`] that can be used to create and destroy windows while
+    /// the event loop is running
+    pub fn get_window_manager(&self) -> window::WindowManager<'a, T> {
+        window::WindowManager::new(self.pending_windows.clone(), self.handle.create_proxy())
+    }
+
     /// Runs the event loop. This function will block until all windows are closed.
-    pub async fn run(self) {
-        let instance = wgpu::Instance::default();
-        // now lets init our windows' surfaces
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: None,
-            })
-            .await;
-        let adapter = match adapter {
-            Ok(adapter) => adapter,
-            Err(e) => {
-                eprintln!(
-                    "No suitable adapter found, taika will now exit. Error:\n{}",
-                    e
-                );
-                return;
-            }
-        };
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: self.render_settings.required_features,
-                required_limits: wgpu::Limits::downlevel_defaults()
-                    .using_resolution(adapter.limits()),
-                memory_hints: wgpu::MemoryHints::Performance,
-                trace: wgpu::Trace::Off,
-            })
-            .await
-            .expect("Failed to create device");
+    ///
+    /// Returns an error if the GPU couldn't be initialized, a window couldn't be created or the
+    /// event loop itself failed.
+    pub async fn run(self) -> Result<(), TaikaError> {
+        let instance = gpu::create_instance(&self.render_settings);
+        let adapter = gpu::request_adapter(&instance, &self.render_settings).await?;
+        let (device, queue, capabilities) =
+            gpu::request_device(&adapter, &self.render_settings).await?;
+        let device_lost = gpu::watch_device_lost(&device, self.handle.create_proxy());
         let windows = self.windows.clone();
+        let main_window = windows.first().cloned();
         let device = Arc::new(Mutex::new(device));
         let queue = Arc::new(Mutex::new(queue));
         let mut state = app_handler::AppState {
             device,
             queue,
             windows,
+            pending_windows: self.pending_windows.clone(),
+            main_window,
+            close_behavior: self.close_behavior,
             adapter,
+            capabilities,
+            device_lost,
+            proxy: self.handle.create_proxy(),
             instance,
             render_settings: self.render_settings.clone(),
+            error: None,
+            shutting_down: false,
+            suspended: true,
         };
-        self.handle.run_app(&mut state).unwrap()
+        self.handle.run_app(&mut state)?;
+        match state.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
     }
 }
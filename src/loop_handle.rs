@@ -0,0 +1,107 @@
+use std::fmt;
+
+use winit::{event_loop::EventLoopProxy, window::WindowId};
+
+use crate::RenderSettings;
+
+/// Events sent to the event loop to wake it up from other threads
+#[derive(Debug)]
+pub enum TaikaEvent<T: 'static = ()> {
+    /// Quit the event loop, closing all windows
+    Quit,
+    /// Windows have been queued for creation or destruction by a
+    /// [`crate::window::WindowManager`]
+    WindowsChanged,
+    /// Redraw a single window, or every window if `None`
+    Redraw(Option<WindowId>),
+    /// Change the render settings, surfaces are reconfigured before their next frame
+    UpdateRenderSettings(RenderSettingsUpdate),
+    /// Sent by taika when the device was lost, the device is recreated when the event loop
+    /// handles the event
+    DeviceLost,
+    /// A user defined event, delivered to [`crate::events::EventHandler::user_event`]
+    User(T),
+}
+
+/// A change to the [`RenderSettings`] of a running event loop
+pub struct RenderSettingsUpdate(Box<dyn FnOnce(&mut RenderSettings) + Send>);
+
+impl RenderSettingsUpdate {
+    pub(crate) fn apply(self, render_settings: &mut RenderSettings) {
+        (self.0)(render_settings)
+    }
+}
+
+impl fmt::Debug for RenderSettingsUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RenderSettingsUpdate")
+    }
+}
+
+/// A cloneable handle to a running [`crate::EventLoop`]. Can be sent to other threads (if `T` is
+/// `Send`), for example to post user events from worker threads or tokio tasks.
+pub struct LoopHandle<T: 'static = ()> {
+    proxy: EventLoopProxy<TaikaEvent<T>>,
+}
+
+impl<T: 'static> Clone for LoopHandle<T> {
+    fn clone(&self) -> Self {
+        LoopHandle {
+            proxy: self.proxy.clone(),
+        }
+    }
+}
+
+impl<T: 'static> LoopHandle<T> {
+    pub(crate) fn new(proxy: EventLoopProxy<TaikaEvent<T>>) -> Self {
+        LoopHandle { proxy }
+    }
+
+    /// Requests the event loop to quit, closing all windows. The event loop is woken up
+    /// immediately. Does nothing if the event loop has already exited
+    pub fn request_quit(&self) {
+        let _ = self.proxy.send_event(TaikaEvent::Quit);
+    }
+
+    /// Requests a new frame to be rendered in every window. Mainly useful with
+    /// [`crate::RedrawMode::OnDemand`]
+    pub fn request_redraw(&self) {
+        let _ = self.proxy.send_event(TaikaEvent::Redraw(None));
+    }
+
+    /// Requests a new frame to be rendered in a single window
+    pub fn request_window_redraw(&self, window_id: WindowId) {
+        let _ = self.proxy.send_event(TaikaEvent::Redraw(Some(window_id)));
+    }
+
+    /// Changes the render settings while the event loop is running, for example to toggle vsync
+    /// or the framerate limit from an options menu. Every window reconfigures its surface before
+    /// its next frame.
+    ///
+    /// Only settings used for surfaces, frame pacing and fixed updates take effect immediately.
+    /// Changing the fixed update settings restarts the fixed timestep. Settings used to
+    /// create the device, like features, limits and backends, are only used if the device has to
+    /// be recreated
+    pub fn update_render_settings(
+        &self,
+        update: impl FnOnce(&mut RenderSettings) + Send + 'static,
+    ) {
+        let _ = self
+            .proxy
+            .send_event(TaikaEvent::UpdateRenderSettings(RenderSettingsUpdate(
+                Box::new(update),
+            )));
+    }
+
+    /// Posts a user event to the event loop. The event is delivered on the main thread to the
+    /// [`crate::events::EventHandler::user_event`] of every window. If the event loop has already
+    /// exited the event is given back
+    pub fn send_event(&self, event: T) -> Result<(), T> {
+        self.proxy
+            .send_event(TaikaEvent::User(event))
+            .map_err(|e| match e.0 {
+                TaikaEvent::User(event) => event,
+                _ => unreachable!(),
+            })
+    }
+}
@@ -1,5 +1,5 @@
 use super::RenderPass;
-use crate::window::TargetProperties;
+use crate::{timing::FrameInfo, window::TargetProperties};
 use std::sync::{Arc, Mutex};
 
 /// A task that can be executed by a compute pass
@@ -48,6 +48,7 @@ impl RenderPass for ComputePass {
         global_bind_group: &'a wgpu::BindGroup,
         _bind_group_layout: &wgpu::BindGroupLayout,
         _target_properties: &TargetProperties,
+        _frame_info: &FrameInfo,
     ) {
         if !self.initialized {
             panic!("ComputePass '{}' not initialized", self.name);
@@ -1,6 +1,7 @@
 use wgpu::RenderPass;
 
-use crate::window::TargetProperties;
+use super::{RenderTarget, ResizeInfo};
+use crate::{timing::FrameInfo, window::TargetProperties};
 
 /// A drawable object that can be drawn to the screen. If using the
 /// [PrimaryDrawPass](crate::rendering::PrimaryDrawPass) the drawables assigned to it will be
@@ -15,10 +16,17 @@ pub trait Drawable {
 
     fn draw(
         &mut self,
-        frame_num: u64,
+        frame_info: &FrameInfo,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         pass: &mut RenderPass, // NOTE: This is a wgpu render pass
         global_bind_group: &wgpu::BindGroup,
     );
+
+    /// Called when a [`RenderTarget`] was reallocated. Drawables sampling the target have to get
+    /// its new bind group. The target is locked during the call
+    fn target_resized(&mut self, _device: &wgpu::Device, _target: &RenderTarget) {}
+
+    /// Called when the window is resized
+    fn resize(&mut self, _device: &wgpu::Device, _queue: &wgpu::Queue, _info: &ResizeInfo) {}
 }
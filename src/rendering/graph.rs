@@ -0,0 +1,458 @@
+//! Declares the resources render passes read and write, so the [`super::DefaultRenderPipeline`]
+//! can order the passes, skip passes whose results are never used and allocate the textures
+//! passed between them.
+//!
+//! Resources are identified by name. [`BACKBUFFER`] is the target the pipeline renders to (the
+//! window surface), every other resource is a transient texture owned by the pipeline. Transient
+//! textures are the size of the backbuffer and are only guaranteed to live for a single frame, two
+//! resources that are never used at the same time might share the same texture.
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use winit::dpi::PhysicalSize;
+
+use super::RenderPass;
+use crate::{gpu, window::TargetProperties, TaikaError};
+
+/// Name of the resource the pipeline renders to. Passes writing it are never culled
+pub const BACKBUFFER: &str = "backbuffer";
+
+/// Describes a transient texture of the render graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransientTexture {
+    /// Format of the texture, `None` uses the view format of the backbuffer
+    pub format: Option<wgpu::TextureFormat>,
+    pub usage: wgpu::TextureUsages,
+}
+
+impl Default for TransientTexture {
+    fn default() -> Self {
+        TransientTexture {
+            format: None,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+}
+
+/// A render pass together with the resources it reads and writes. The first written resource is
+/// the `target` passed to [`RenderPass::render`], all of them are available through
+/// [`RenderPass::bind_resources`]
+pub struct GraphPass {
+    pub(super) name: String,
+    pub(super) pass: Arc<Mutex<dyn RenderPass>>,
+    pub(super) reads: Vec<String>,
+    pub(super) writes: Vec<String>,
+}
+
+impl GraphPass {
+    /// The name is used in error messages
+    pub fn new(name: &str, pass: Arc<Mutex<dyn RenderPass>>) -> Self {
+        GraphPass {
+            name: name.to_string(),
+            pass,
+            reads: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    /// Declares a resource the pass reads. The pass runs after every pass writing it
+    pub fn reads(mut self, resource: &str) -> Self {
+        self.reads.push(resource.to_string());
+        self
+    }
+
+    /// Declares a resource the pass writes. Passes writing the same resource run in the order they
+    /// were added to the pipeline
+    pub fn writes(mut self, resource: &str) -> Self {
+        self.writes.push(resource.to_string());
+        self
+    }
+
+    /// Returns the name of the pass
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The textures a pass declared in its [`GraphPass`], looked up by resource name
+pub struct GraphResources<'a> {
+    pub(super) views: Vec<(&'a str, &'a wgpu::TextureView)>,
+}
+
+impl<'a> GraphResources<'a> {
+    /// Returns the view of a resource the pass reads or writes
+    pub fn get(&self, resource: &str) -> Option<&'a wgpu::TextureView> {
+        self.views
+            .iter()
+            .find(|(name, _)| *name == resource)
+            .map(|(_, view)| *view)
+    }
+}
+
+/// The order passes are run in and the textures backing the transient resources
+pub(super) struct CompiledGraph {
+    /// Indices of the passes that aren't culled, in the order they run
+    pub order: Vec<usize>,
+    /// The textures that have to be allocated, several resources might share one
+    pub slots: Vec<TransientTexture>,
+    pub resource_slots: HashMap<String, usize>,
+}
+
+impl CompiledGraph {
+    /// Returns the target properties of a pass rendering to `resource`. The sample count of the
+    /// backbuffer is selected again if the transient texture has a different format
+    pub fn target_properties(
+        &self,
+        device: &wgpu::Device,
+        resource: Option<&str>,
+        target_properties: &TargetProperties,
+    ) -> TargetProperties {
+        let format = resource
+            .and_then(|resource| self.resource_slots.get(resource))
+            .map(|&slot| {
+                self.slots[slot]
+                    .format
+                    .unwrap_or(target_properties.view_format)
+            });
+        match format {
+            Some(format) => TargetProperties {
+                format,
+                view_format: format,
+                sample_count: if format == target_properties.view_format {
+                    target_properties.sample_count
+                } else {
+                    gpu::select_sample_count(None, device, format, target_properties.sample_count)
+                },
+                depth_format: None,
+                ..target_properties.clone()
+            },
+            None => target_properties.clone(),
+        }
+    }
+}
+
+pub(super) fn compile(
+    passes: &[GraphPass],
+    transients: &HashMap<String, TransientTexture>,
+) -> Result<CompiledGraph, TaikaError> {
+    let mut writers: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, pass) in passes.iter().enumerate() {
+        for resource in &pass.writes {
+            writers.entry(resource).or_default().push(i);
+        }
+    }
+
+    // Writers of a resource run in insertion order, readers run after all writers
+    let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); passes.len()];
+    for writers in writers.values() {
+        for pair in writers.windows(2) {
+            dependencies[pair[1]].push(pair[0]);
+        }
+    }
+    for (i, pass) in passes.iter().enumerate() {
+        for resource in &pass.reads {
+            if pass.writes.contains(resource) {
+                continue;
+            }
+            match writers.get(resource.as_str()) {
+                Some(writers) => dependencies[i].extend(writers),
+                None if resource == BACKBUFFER => {}
+                None => {
+                    return Err(TaikaError::RenderGraphMissingInput {
+                        pass: pass.name.clone(),
+                        resource: resource.clone(),
+                    })
+                }
+            }
+        }
+    }
+
+    // Kahn's algorithm, preferring the insertion order when passes don't depend on each other
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); passes.len()];
+    let mut remaining: Vec<usize> = vec![0; passes.len()];
+    for (i, dependencies) in dependencies.iter_mut().enumerate() {
+        dependencies.sort_unstable();
+        dependencies.dedup();
+        remaining[i] = dependencies.len();
+        for &dependency in dependencies.iter() {
+            dependents[dependency].push(i);
+        }
+    }
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..passes.len())
+        .filter(|&i| remaining[i] == 0)
+        .map(Reverse)
+        .collect();
+    let mut sorted = Vec::with_capacity(passes.len());
+    while let Some(Reverse(i)) = ready.pop() {
+        sorted.push(i);
+        for &dependent in &dependents[i] {
+            remaining[dependent] -= 1;
+            if remaining[dependent] == 0 {
+                ready.push(Reverse(dependent));
+            }
+        }
+    }
+    if sorted.len() != passes.len() {
+        return Err(TaikaError::RenderGraphCycle {
+            passes: (0..passes.len())
+                .filter(|&i| remaining[i] > 0)
+                .map(|i| passes[i].name.clone())
+                .collect(),
+        });
+    }
+
+    // Only passes contributing to the backbuffer are kept. Passes without any declared writes
+    // might have side effects the graph doesn't know about, so they are kept as well
+    let mut used = vec![false; passes.len()];
+    let mut stack: Vec<usize> = (0..passes.len())
+        .filter(|&i| {
+            passes[i].writes.is_empty() || passes[i].writes.iter().any(|w| w == BACKBUFFER)
+        })
+        .collect();
+    while let Some(i) = stack.pop() {
+        if !used[i] {
+            used[i] = true;
+            stack.extend(&dependencies[i]);
+        }
+    }
+    let order: Vec<usize> = sorted.into_iter().filter(|&i| used[i]).collect();
+
+    // Resources that are never alive at the same time and have the same description share a
+    // texture
+    let mut lifetimes: Vec<(&str, usize, usize)> = Vec::new();
+    for (position, &i) in order.iter().enumerate() {
+        for resource in passes[i].reads.iter().chain(&passes[i].writes) {
+            if resource == BACKBUFFER {
+                continue;
+            }
+            match lifetimes.iter_mut().find(|(name, _, _)| name == resource) {
+                Some(lifetime) => lifetime.2 = position,
+                None => lifetimes.push((resource, position, position)),
+            }
+        }
+    }
+    let mut slots: Vec<TransientTexture> = Vec::new();
+    let mut slot_last_use: Vec<usize> = Vec::new();
+    let mut resource_slots = HashMap::new();
+    for (resource, first_use, last_use) in lifetimes {
+        let description = transients.get(resource).cloned().unwrap_or_default();
+        let free_slot = (0..slots.len())
+            .find(|&slot| slot_last_use[slot] < first_use && slots[slot] == description);
+        let slot = match free_slot {
+            Some(slot) => slot,
+            None => {
+                slots.push(description);
+                slot_last_use.push(0);
+                slots.len() - 1
+            }
+        };
+        slot_last_use[slot] = last_use;
+        resource_slots.insert(resource.to_string(), slot);
+    }
+
+    Ok(CompiledGraph {
+        order,
+        slots,
+        resource_slots,
+    })
+}
+
+/// A texture backing one or more transient resources
+pub(super) struct GraphTexture {
+    pub view: wgpu::TextureView,
+    size: PhysicalSize<u32>,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+}
+
+impl GraphTexture {
+    pub fn new(
+        device: &wgpu::Device,
+        size: PhysicalSize<u32>,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Taika transient texture"),
+            size: wgpu::Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+        GraphTexture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            size,
+            format,
+            usage,
+        }
+    }
+
+    pub fn matches(
+        &self,
+        size: PhysicalSize<u32>,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> bool {
+        self.size == size && self.format == format && self.usage == usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopPass;
+
+    impl RenderPass for NoopPass {
+        fn render(
+            &mut self,
+            _device: &wgpu::Device,
+            _encoder: &mut wgpu::CommandEncoder,
+            _queue: &wgpu::Queue,
+            _target: &wgpu::TextureView,
+            _global_bind_group: &wgpu::BindGroup,
+            _bind_group_layout: &wgpu::BindGroupLayout,
+            _target_properties: &TargetProperties,
+            _frame_info: &crate::timing::FrameInfo,
+        ) {
+        }
+
+        fn init(
+            &mut self,
+            _device: &wgpu::Device,
+            _bind_group_layout: &wgpu::BindGroupLayout,
+            _target_properties: &TargetProperties,
+        ) {
+        }
+    }
+
+    fn pass(name: &str) -> GraphPass {
+        GraphPass::new(name, Arc::new(Mutex::new(NoopPass)))
+    }
+
+    fn compile_passes(passes: &[GraphPass]) -> Result<CompiledGraph, TaikaError> {
+        compile(passes, &HashMap::new())
+    }
+
+    #[test]
+    fn backbuffer_passes_keep_insertion_order() {
+        let passes = [
+            pass("a").writes(BACKBUFFER),
+            pass("b").writes(BACKBUFFER),
+            pass("c").writes(BACKBUFFER),
+        ];
+        let compiled = compile_passes(&passes).unwrap();
+        assert_eq!(compiled.order, vec![0, 1, 2]);
+        assert!(compiled.slots.is_empty());
+    }
+
+    #[test]
+    fn readers_run_after_writers() {
+        let passes = [
+            pass("composite").reads("scene").writes(BACKBUFFER),
+            pass("scene").writes("scene"),
+        ];
+        let compiled = compile_passes(&passes).unwrap();
+        assert_eq!(compiled.order, vec![1, 0]);
+    }
+
+    #[test]
+    fn writers_of_a_resource_keep_insertion_order() {
+        // "second" has no other dependencies, but still has to run after "first" writes "a"
+        let passes = [
+            pass("first").reads("b").writes("a"),
+            pass("second").writes("a"),
+            pass("b").writes("b"),
+            pass("main").reads("a").writes(BACKBUFFER),
+        ];
+        let compiled = compile_passes(&passes).unwrap();
+        assert_eq!(compiled.order, vec![2, 0, 1, 3]);
+    }
+
+    #[test]
+    fn unused_passes_are_culled() {
+        let passes = [
+            pass("unused").writes("debug"),
+            pass("side effects"),
+            pass("main").writes(BACKBUFFER),
+        ];
+        let compiled = compile_passes(&passes).unwrap();
+        assert_eq!(compiled.order, vec![1, 2]);
+        assert!(!compiled.resource_slots.contains_key("debug"));
+    }
+
+    #[test]
+    fn missing_input_is_an_error() {
+        let passes = [pass("main").reads("scene").writes(BACKBUFFER)];
+        match compile_passes(&passes) {
+            Err(TaikaError::RenderGraphMissingInput { pass, resource }) => {
+                assert_eq!(pass, "main");
+                assert_eq!(resource, "scene");
+            }
+            _ => panic!("expected RenderGraphMissingInput"),
+        }
+    }
+
+    #[test]
+    fn cycle_is_an_error() {
+        let passes = [
+            pass("a").reads("b").writes("a"),
+            pass("b").reads("a").writes("b"),
+            pass("main").reads("a").writes(BACKBUFFER),
+        ];
+        match compile_passes(&passes) {
+            Err(TaikaError::RenderGraphCycle { passes }) => {
+                assert!(passes.contains(&"a".to_string()));
+                assert!(passes.contains(&"b".to_string()));
+            }
+            _ => panic!("expected RenderGraphCycle"),
+        }
+    }
+
+    #[test]
+    fn overlapping_resources_are_not_aliased() {
+        // "a" is last used by the pass that first uses "b", so they can't share a texture. "c" is
+        // first used after "a" is done and reuses its texture
+        let passes = [
+            pass("first").writes("a"),
+            pass("second").reads("a").writes("b"),
+            pass("third").reads("b").writes("c"),
+            pass("main").reads("c").writes(BACKBUFFER),
+        ];
+        let compiled = compile_passes(&passes).unwrap();
+        let slot = |resource: &str| compiled.resource_slots[resource];
+        assert_eq!(compiled.slots.len(), 2);
+        assert_ne!(slot("a"), slot("b"));
+        assert_ne!(slot("b"), slot("c"));
+        assert_eq!(slot("a"), slot("c"));
+    }
+
+    #[test]
+    fn different_descriptions_are_not_aliased() {
+        let passes = [
+            pass("first").writes("a"),
+            pass("second").reads("a").writes("b"),
+            pass("third").reads("b").writes("c"),
+            pass("main").reads("c").writes(BACKBUFFER),
+        ];
+        let transients = HashMap::from([(
+            "c".to_string(),
+            TransientTexture {
+                format: Some(wgpu::TextureFormat::R32Float),
+                ..Default::default()
+            },
+        )]);
+        let compiled = compile(&passes, &transients).unwrap();
+        assert_eq!(compiled.slots.len(), 3);
+    }
+}
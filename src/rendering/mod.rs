@@ -1,20 +1,35 @@
 use std::{
+    collections::HashMap,
     rc::Rc,
     sync::{Arc, Mutex},
 };
 
 use wgpu::{CommandEncoder, Device, Queue};
+use winit::dpi::PhysicalSize;
 
-use crate::window::TargetProperties;
+use crate::{timing::FrameInfo, window::TargetProperties, TaikaError};
 
 pub mod compute;
 pub mod drawable;
+pub mod graph;
 mod primary_draw_pass;
 pub mod shader;
+mod target;
 pub mod vertex;
-pub use primary_draw_pass::PrimaryDrawPass;
+pub use primary_draw_pass::{DepthStencil, PrimaryDrawPass};
+pub use target::{RenderTarget, TargetSize};
+
+/// Passed to the resize callbacks of pipelines, passes and drawables
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResizeInfo {
+    pub old_size: PhysicalSize<u32>,
+    pub new_size: PhysicalSize<u32>,
+    /// The scale factor of the window, see [`winit::window::Window::scale_factor`]
+    pub scale_factor: f64,
+}
 
 pub trait RenderPass {
+    #[allow(clippy::too_many_arguments)]
     fn render(
         &mut self,
         device: &Device,
@@ -24,6 +39,7 @@ pub trait RenderPass {
         global_bind_group: &wgpu::BindGroup,
         bind_group_layout: &wgpu::BindGroupLayout,
         target_properties: &TargetProperties,
+        frame_info: &FrameInfo,
     );
 
     fn init(
@@ -32,6 +48,20 @@ pub trait RenderPass {
         bind_group_layout: &wgpu::BindGroupLayout,
         target_properties: &TargetProperties,
     );
+
+    /// Called by the [`DefaultRenderPipeline`] before every `render` with the textures the pass
+    /// declared in its [`graph::GraphPass`]. The views can change between frames, for example when
+    /// the window is resized
+    fn bind_resources(&mut self, _device: &Device, _resources: &graph::GraphResources) {}
+
+    /// Called when a [`RenderTarget`] of the pipeline was reallocated, for example because the
+    /// window was resized. Bind groups using the target have to be recreated. The target is locked
+    /// during the call
+    fn target_resized(&mut self, _device: &Device, _target: &RenderTarget) {}
+
+    /// Called when the window is resized. `TargetProperties::size` passed to the next `render`
+    /// already has the new size
+    fn resize(&mut self, _device: &Device, _queue: &Queue, _info: &ResizeInfo) {}
 }
 
 pub trait RenderPipeline {
@@ -42,6 +72,7 @@ pub trait RenderPipeline {
         queue: &Queue,
         target: &wgpu::TextureView,
         target_properties: &TargetProperties,
+        frame_info: &FrameInfo,
     );
 
     fn init(
@@ -50,12 +81,28 @@ pub trait RenderPipeline {
         bind_group_layout: &wgpu::BindGroupLayout,
         target_properties: &TargetProperties,
     );
+
+    /// Called when all GPU resources have to be recreated, for example after the device was lost.
+    /// The pipeline should initialize itself again before rendering the next frame
+    fn reset(&mut self) {}
+
+    /// Called when the window is resized, before the next frame is rendered
+    fn resize(&mut self, _device: &Device, _queue: &Queue, _info: &ResizeInfo) {}
 }
 
+/// Runs its render passes as a render graph, see [`graph`]. Passes added with
+/// [`DefaultRenderPipeline::add_render_pass`] write to the backbuffer and run in the order they
+/// were added
 pub struct DefaultRenderPipeline {
-    render_passes: Vec<Arc<Mutex<dyn RenderPass>>>,
+    render_passes: Vec<graph::GraphPass>,
+    transients: HashMap<String, graph::TransientTexture>,
+    render_targets: Vec<Arc<Mutex<RenderTarget>>>,
+    compiled: Option<graph::CompiledGraph>,
+    textures: Vec<graph::GraphTexture>,
     global_bind_group: Box<dyn GlobalBindGroup>,
     initialized: bool,
+    /// Why the graph couldn't be compiled, frames are skipped until the graph changes
+    graph_error: Option<TaikaError>,
     #[allow(dead_code)]
     name: String,
 }
@@ -64,14 +111,111 @@ impl DefaultRenderPipeline {
     pub fn new(global_bind_group: Box<dyn GlobalBindGroup>, name: &str) -> Self {
         DefaultRenderPipeline {
             render_passes: Vec::new(),
+            transients: HashMap::new(),
+            render_targets: Vec::new(),
+            compiled: None,
+            textures: Vec::new(),
             initialized: false,
+            graph_error: None,
             global_bind_group,
             name: name.to_string(),
         }
     }
 
     pub fn add_render_pass(&mut self, render_pass: Arc<Mutex<dyn RenderPass>>) {
-        self.render_passes.push(render_pass);
+        let name = format!("pass {}", self.render_passes.len());
+        self.add_graph_pass(graph::GraphPass::new(&name, render_pass).writes(graph::BACKBUFFER));
+    }
+
+    /// Adds a pass with its declared reads and writes. Changing the graph initializes all passes
+    /// again before the next frame
+    pub fn add_graph_pass(&mut self, pass: graph::GraphPass) {
+        self.render_passes.push(pass);
+        self.invalidate();
+    }
+
+    /// Describes a transient texture of the graph. Resources that are written without being
+    /// described use [`graph::TransientTexture::default`]
+    pub fn add_transient_texture(&mut self, name: &str, texture: graph::TransientTexture) {
+        self.transients.insert(name.to_string(), texture);
+        self.invalidate();
+    }
+
+    /// Registers a render target so the pipeline allocates it and keeps its size up to date
+    pub fn add_render_target(&mut self, target: Arc<Mutex<RenderTarget>>) {
+        self.render_targets.push(target);
+    }
+
+    /// Removes a render target from the pipeline
+    pub fn remove_render_target(&mut self, target: &Arc<Mutex<RenderTarget>>) {
+        self.render_targets.retain(|t| !Arc::ptr_eq(t, target));
+    }
+
+    /// Orders the passes and assigns textures to the transient resources. This is done
+    /// automatically when the pipeline is initialized. If the graph is invalid frames are skipped
+    /// until the graph changes, the error is available through
+    /// [`DefaultRenderPipeline::get_graph_error`]. Call this after setting up the graph to handle
+    /// the error right away instead
+    pub fn compile(&mut self) -> Result<(), TaikaError> {
+        self.compiled = Some(graph::compile(&self.render_passes, &self.transients)?);
+        self.graph_error = None;
+        Ok(())
+    }
+
+    /// Returns why the graph couldn't be compiled, `None` if it is valid or hasn't been compiled
+    /// yet
+    pub fn get_graph_error(&self) -> Option<&TaikaError> {
+        self.graph_error.as_ref()
+    }
+
+    fn invalidate(&mut self) {
+        self.compiled = None;
+        self.initialized = false;
+        self.graph_error = None;
+    }
+
+    /// Compiles the graph if it changed, returns false if it is invalid
+    fn ensure_compiled(&mut self) -> bool {
+        if self.compiled.is_some() {
+            return true;
+        }
+        if self.graph_error.is_some() {
+            return false;
+        }
+        match self.compile() {
+            Ok(()) => true,
+            Err(error) => {
+                self.graph_error = Some(error);
+                false
+            }
+        }
+    }
+
+    /// Makes sure every transient texture exists with the current size and format
+    fn allocate_textures(
+        &mut self,
+        device: &Device,
+        frame_info: &FrameInfo,
+        target_properties: &TargetProperties,
+    ) {
+        let compiled = self.compiled.as_ref().unwrap();
+        self.textures.truncate(compiled.slots.len());
+        for (i, slot) in compiled.slots.iter().enumerate() {
+            let format = slot.format.unwrap_or(target_properties.view_format);
+            match self.textures.get(i) {
+                Some(texture) if texture.matches(frame_info.size, format, slot.usage) => {}
+                Some(_) => {
+                    self.textures[i] =
+                        graph::GraphTexture::new(device, frame_info.size, format, slot.usage)
+                }
+                None => self.textures.push(graph::GraphTexture::new(
+                    device,
+                    frame_info.size,
+                    format,
+                    slot.usage,
+                )),
+            }
+        }
     }
 }
 
@@ -83,26 +227,85 @@ impl RenderPipeline for DefaultRenderPipeline {
         queue: &Queue,
         target: &wgpu::TextureView,
         target_properties: &TargetProperties,
+        frame_info: &FrameInfo,
     ) {
+        if !self.ensure_compiled() {
+            return;
+        }
+        // Targets are allocated before the passes are initialized, so they can already be used
+        // while initializing. Passes are only notified about targets resized after that
+        let mut resized_targets = Vec::new();
+        for render_target in &self.render_targets {
+            if render_target
+                .lock()
+                .unwrap()
+                .update(device, frame_info.size)
+            {
+                resized_targets.push(render_target.clone());
+            }
+        }
         if !self.initialized {
             let bind_group_layout = self.global_bind_group.get_layout(device);
             self.init(device, &bind_group_layout, target_properties);
             self.initialized = true;
+        } else {
+            for render_target in resized_targets {
+                let render_target = render_target.lock().unwrap();
+                for pass in &self.render_passes {
+                    pass.pass
+                        .lock()
+                        .unwrap()
+                        .target_resized(device, &render_target);
+                }
+            }
         }
+        self.allocate_textures(device, frame_info, target_properties);
         self.global_bind_group.pre_render(device, queue);
-        for render_pass in &mut self.render_passes {
-            render_pass.lock().unwrap().render(
+        let compiled = self.compiled.as_ref().unwrap();
+        let view = |resource: &str| match compiled.resource_slots.get(resource) {
+            Some(&slot) => &self.textures[slot].view,
+            None => target,
+        };
+        for &i in &compiled.order {
+            let pass = &self.render_passes[i];
+            let resources = graph::GraphResources {
+                views: pass
+                    .reads
+                    .iter()
+                    .chain(&pass.writes)
+                    .map(|resource| (resource.as_str(), view(resource)))
+                    .collect(),
+            };
+            let output = pass.writes.first().map(String::as_str);
+            let mut render_pass = pass.pass.lock().unwrap();
+            render_pass.bind_resources(device, &resources);
+            render_pass.render(
                 device,
                 encoder,
                 queue,
-                target,
+                output.map_or(target, view),
                 &self.global_bind_group.get_group(),
                 self.global_bind_group.get_layout(device).as_ref(),
-                target_properties,
+                &compiled.target_properties(device, output, target_properties),
+                frame_info,
             )
         }
     }
 
+    fn resize(&mut self, device: &Device, queue: &Queue, info: &ResizeInfo) {
+        for pass in &self.render_passes {
+            pass.pass.lock().unwrap().resize(device, queue, info);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.initialized = false;
+        self.textures.clear();
+        for render_target in &self.render_targets {
+            render_target.lock().unwrap().release();
+        }
+    }
+
     fn init(
         &mut self,
         device: &Device,
@@ -110,10 +313,18 @@ impl RenderPipeline for DefaultRenderPipeline {
         target_properties: &TargetProperties,
     ) {
         self.global_bind_group.init(device);
-        for pass in self.render_passes.iter_mut() {
-            pass.lock()
+        self.ensure_compiled();
+        let compiled = self.compiled.as_ref();
+        for pass in self.render_passes.iter() {
+            let output = pass.writes.first().map(String::as_str);
+            let target_properties = match compiled {
+                Some(compiled) => compiled.target_properties(device, output, target_properties),
+                None => target_properties.clone(),
+            };
+            pass.pass
+                .lock()
                 .unwrap()
-                .init(device, bind_group_layout, target_properties);
+                .init(device, bind_group_layout, &target_properties);
         }
     }
 }
@@ -4,10 +4,11 @@ use std::{
 };
 
 use wgpu::{CommandEncoder, Device, Queue};
+use winit::dpi::PhysicalSize;
 
-use crate::window::TargetProperties;
+use crate::{gpu, timing::FrameInfo, window::TargetProperties};
 
-use super::{drawable::Drawable, RenderPass};
+use super::{drawable::Drawable, RenderPass, RenderTarget, ResizeInfo};
 
 /// A basic [`RenderPass`] that draws drawables in order of their z value
 pub struct PrimaryDrawPass {
@@ -15,7 +16,114 @@ pub struct PrimaryDrawPass {
     new_drawables: Vec<(Arc<Mutex<dyn Drawable>>, u32)>,
     name: String,
     target: Option<Arc<Mutex<wgpu::TextureView>>>,
+    render_target: Option<Arc<Mutex<RenderTarget>>>,
     clear_color: wgpu::Color,
+    multisample_target: Option<ManagedTexture>,
+    depth_stencil: Option<DepthStencil>,
+    depth_target: Option<ManagedTexture>,
+    /// Set when the drawables have to be initialized again because the attachments changed
+    drawables_outdated: bool,
+}
+
+/// Depth and stencil buffer settings of a [`PrimaryDrawPass`]
+#[derive(Debug, Clone)]
+pub struct DepthStencil {
+    /// Format of the depth/stencil texture, this decides whether the pass has a depth buffer, a
+    /// stencil buffer or both
+    pub format: wgpu::TextureFormat,
+    /// Used if the format has a depth aspect
+    pub depth_ops: wgpu::Operations<f32>,
+    /// Used if the format has a stencil aspect
+    pub stencil_ops: wgpu::Operations<u32>,
+}
+
+impl Default for DepthStencil {
+    fn default() -> Self {
+        DepthStencil {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            },
+            stencil_ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0),
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+}
+
+/// A texture owned by the pass that is recreated whenever the window size or the target
+/// properties change. Used for the multisampled color texture, which is resolved into the pass
+/// target, and the depth/stencil texture
+struct ManagedTexture {
+    view: wgpu::TextureView,
+    size: PhysicalSize<u32>,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+}
+
+impl ManagedTexture {
+    fn new(
+        device: &Device,
+        label: &str,
+        size: PhysicalSize<u32>,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        ManagedTexture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            size,
+            format,
+            sample_count,
+        }
+    }
+
+    /// Returns a texture matching the parameters, reusing the existing one if possible
+    fn update(
+        texture: &mut Option<ManagedTexture>,
+        device: &Device,
+        label: &str,
+        size: PhysicalSize<u32>,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        if !texture
+            .as_ref()
+            .is_some_and(|t| t.matches(size, format, sample_count))
+        {
+            *texture = Some(ManagedTexture::new(
+                device,
+                label,
+                size,
+                format,
+                sample_count,
+            ));
+        }
+    }
+
+    fn matches(
+        &self,
+        size: PhysicalSize<u32>,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> bool {
+        self.size == size && self.format == format && self.sample_count == sample_count
+    }
 }
 
 struct DrawableElement {
@@ -51,7 +159,12 @@ impl PrimaryDrawPass {
             new_drawables: Vec::new(),
             name: name.to_string(),
             target,
+            render_target: None,
             clear_color: wgpu::Color::TRANSPARENT,
+            multisample_target: None,
+            depth_stencil: None,
+            depth_target: None,
+            drawables_outdated: false,
         }
     }
 
@@ -69,16 +182,70 @@ impl PrimaryDrawPass {
         self.new_drawables.retain(|d| !Arc::ptr_eq(&d.0, &drawable));
     }
 
-    /// Set the target of the render pass. If no target is provided the pass will use the previous or default target of the [`super::RenderPipeline`]
+    /// Set the target of the render pass. If no target is provided the pass will use the previous or default target of the [`super::RenderPipeline`].
+    /// With MSAA or a depth buffer enabled the target has to be the same size as the window, use a
+    /// [`RenderTarget`] with [`PrimaryDrawPass::set_render_target`] for targets of other sizes
     pub fn set_target(&mut self, target: Option<Arc<Mutex<wgpu::TextureView>>>) {
         self.target = target;
     }
 
+    /// Renders into a [`RenderTarget`] instead of the target set with
+    /// [`PrimaryDrawPass::set_target`]. The target has to be registered with the pipeline. The
+    /// drawables are initialized again with the format of the new target
+    pub fn set_render_target(&mut self, render_target: Option<Arc<Mutex<RenderTarget>>>) {
+        self.render_target = render_target;
+        self.drawables_outdated = true;
+    }
+
     /// Set the clear color (or background color) of the render pass
     pub fn set_clear_color(&mut self, color: wgpu::Color) {
         self.clear_color = color;
     }
 
+    /// Enables a depth and/or stencil buffer for this pass, or disables it with `None`. The buffer
+    /// is sized to the window and its format is passed to the drawables through
+    /// [`TargetProperties::depth_format`], they are initialized again when it changes
+    pub fn set_depth_stencil(&mut self, depth_stencil: Option<DepthStencil>) {
+        let format_changed = self.depth_stencil.as_ref().map(|d| d.format)
+            != depth_stencil.as_ref().map(|d| d.format);
+        self.drawables_outdated |= format_changed;
+        self.depth_stencil = depth_stencil;
+    }
+
+    /// Returns the depth/stencil settings of this pass
+    pub fn get_depth_stencil(&self) -> Option<&DepthStencil> {
+        self.depth_stencil.as_ref()
+    }
+
+    /// The target properties the drawables of this pass render with. The sample count of the
+    /// window is only guaranteed to be supported by its own format, so it is selected again for
+    /// render targets
+    fn drawable_target_properties(
+        &self,
+        device: &Device,
+        target_properties: &TargetProperties,
+    ) -> TargetProperties {
+        let mut target_properties = TargetProperties {
+            depth_format: self.depth_stencil.as_ref().map(|d| d.format),
+            ..target_properties.clone()
+        };
+        if let Some(render_target) = &self.render_target {
+            let render_target = render_target.lock().unwrap();
+            target_properties.format = render_target.get_format();
+            target_properties.view_format = render_target.get_format();
+            target_properties.sample_count = gpu::select_sample_count(
+                None,
+                device,
+                render_target.get_format(),
+                target_properties.sample_count,
+            );
+            if let Some(size) = render_target.get_size() {
+                target_properties.size = size;
+            }
+        }
+        target_properties
+    }
+
     /// Returns the number of drawables assigned to this [`RenderPass`]
     pub fn drawable_count(&self) -> usize {
         self.drawables.len()
@@ -95,11 +262,16 @@ impl RenderPass for PrimaryDrawPass {
         global_bind_group: &wgpu::BindGroup,
         bind_group_layout: &wgpu::BindGroupLayout,
         target_properties: &TargetProperties,
+        frame_info: &FrameInfo,
     ) {
+        let drawable_target_properties = self.drawable_target_properties(device, target_properties);
+        if self.drawables_outdated {
+            self.init(device, bind_group_layout, target_properties);
+        }
         for d in self.new_drawables.drain(..) {
             d.0.lock()
                 .unwrap()
-                .init(device, bind_group_layout, target_properties);
+                .init(device, bind_group_layout, &drawable_target_properties);
             self.drawables.push(DrawableElement {
                 drawable: d.0,
                 z: d.1,
@@ -109,33 +281,89 @@ impl RenderPass for PrimaryDrawPass {
         for d in self.drawables.iter() {
             drawables.push(d.drawable.lock().unwrap());
         }
-        let lock = if self.target.is_some() {
-            Some(self.target.as_ref().unwrap().lock().unwrap())
+        let render_target = self
+            .render_target
+            .as_ref()
+            .map(|target| target.lock().unwrap());
+        let lock = self.target.as_ref().map(|target| target.lock().unwrap());
+        let (target, size) = if let Some(render_target) = render_target.as_ref() {
+            (
+                render_target.get_view(),
+                render_target.get_size().unwrap_or(frame_info.size),
+            )
+        } else if let Some(lock) = lock.as_ref() {
+            (&**lock, frame_info.size)
         } else {
-            None
+            (target, frame_info.size)
         };
-        let target = if let Some(lock) = lock.as_ref() {
-            lock
+        // With MSAA the drawables render into a multisampled texture of the same size as the
+        // target, which is resolved into the actual target at the end of the pass
+        let sample_count = drawable_target_properties.sample_count;
+        if sample_count > 1 {
+            ManagedTexture::update(
+                &mut self.multisample_target,
+                device,
+                "Taika multisample target",
+                size,
+                drawable_target_properties.view_format,
+                sample_count,
+            );
         } else {
-            target
+            self.multisample_target = None;
+        }
+        match &self.depth_stencil {
+            Some(depth_stencil) => ManagedTexture::update(
+                &mut self.depth_target,
+                device,
+                "Taika depth target",
+                size,
+                depth_stencil.format,
+                sample_count,
+            ),
+            None => self.depth_target = None,
+        }
+        let depth_stencil_attachment = self
+            .depth_stencil
+            .as_ref()
+            .zip(self.depth_target.as_ref())
+            .map(
+                |(depth_stencil, depth_target)| wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_target.view,
+                    depth_ops: depth_stencil
+                        .format
+                        .has_depth_aspect()
+                        .then_some(depth_stencil.depth_ops),
+                    stencil_ops: depth_stencil
+                        .format
+                        .has_stencil_aspect()
+                        .then_some(depth_stencil.stencil_ops),
+                },
+            );
+        let (view, resolve_target, store) = match &self.multisample_target {
+            Some(multisample_target) => (
+                &multisample_target.view,
+                Some(target),
+                wgpu::StoreOp::Discard,
+            ),
+            None => (target, None, wgpu::StoreOp::Store),
         };
         // Start wgpu render pass
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some(&self.name),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: target,
-                resolve_target: None,
+                view,
+                resolve_target,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(self.clear_color),
-                    store: wgpu::StoreOp::Store,
+                    store,
                 },
             })],
-            depth_stencil_attachment: None,
+            depth_stencil_attachment,
             timestamp_writes: None,
             occlusion_query_set: None,
         });
         for d in drawables.iter_mut() {
-            d.draw(0, device, queue, &mut rpass, global_bind_group);
+            d.draw(frame_info, device, queue, &mut rpass, global_bind_group);
         }
     }
 
@@ -145,11 +373,29 @@ impl RenderPass for PrimaryDrawPass {
         bind_group_layout: &wgpu::BindGroupLayout,
         target_properties: &TargetProperties,
     ) {
+        // The pipeline initializes its passes again on a new device after the old one was lost, so
+        // the pass textures may belong to a device that no longer exists
+        self.multisample_target = None;
+        self.depth_target = None;
+        let target_properties = self.drawable_target_properties(device, target_properties);
         for d in self.drawables.iter() {
             d.drawable
                 .lock()
                 .unwrap()
-                .init(device, bind_group_layout, target_properties);
+                .init(device, bind_group_layout, &target_properties);
+        }
+        self.drawables_outdated = false;
+    }
+
+    fn resize(&mut self, device: &Device, queue: &Queue, info: &ResizeInfo) {
+        for d in self.drawables.iter() {
+            d.drawable.lock().unwrap().resize(device, queue, info);
+        }
+    }
+
+    fn target_resized(&mut self, device: &Device, target: &RenderTarget) {
+        for d in self.drawables.iter() {
+            d.drawable.lock().unwrap().target_resized(device, target);
         }
     }
 }
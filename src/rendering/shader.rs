@@ -1,4 +1,4 @@
-use crate::asset_management::ResolvableAsset;
+use crate::{asset_management::ResolvableAsset, TaikaError};
 
 /// Wrapper around [`wgpu::ShaderModule`]
 pub struct Shader {
@@ -6,15 +6,21 @@ pub struct Shader {
 }
 
 impl Shader {
-    /// Creates a new shader from a source file
-    pub fn new(device: &wgpu::Device, source: Box<dyn ResolvableAsset>) -> Self {
+    /// Creates a new shader from a source file. Fails if the source can't be resolved or isn't
+    /// valid UTF-8
+    pub fn new(
+        device: &wgpu::Device,
+        source: Box<dyn ResolvableAsset>,
+    ) -> Result<Self, TaikaError> {
+        let code = String::from_utf8(source.resolve()?).map_err(|e| TaikaError::InvalidAsset {
+            name: source.get_name().to_string(),
+            reason: e.to_string(),
+        })?;
         let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(
-                String::from_utf8(source.resolve()).unwrap().as_str().into(),
-            ),
+            label: Some(source.get_name()),
+            source: wgpu::ShaderSource::Wgsl(code.as_str().into()),
         });
-        Shader { module }
+        Ok(Shader { module })
     }
 
     /// Returns the [`wgpu::ShaderModule`] of the shader
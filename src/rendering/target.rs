@@ -0,0 +1,224 @@
+use winit::dpi::PhysicalSize;
+
+/// The size of a [`RenderTarget`], relative to the size of the window (or headless target) it is
+/// rendered in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetSize {
+    /// The same size as the window
+    Full,
+    /// The size of the window multiplied by the factor, for example `0.5` for half resolution
+    Scaled(f32),
+    /// A fixed size in pixels, independent of the window
+    Fixed(u32, u32),
+}
+
+impl TargetSize {
+    /// Returns the size in pixels for a window of the given size, never smaller than 1x1
+    pub fn resolve(&self, window_size: PhysicalSize<u32>) -> PhysicalSize<u32> {
+        let (width, height) = match *self {
+            TargetSize::Full => (window_size.width, window_size.height),
+            TargetSize::Scaled(factor) => (
+                (window_size.width as f32 * factor).round() as u32,
+                (window_size.height as f32 * factor).round() as u32,
+            ),
+            TargetSize::Fixed(width, height) => (width, height),
+        };
+        PhysicalSize::new(width.max(1), height.max(1))
+    }
+}
+
+/// An offscreen texture that can be rendered to and sampled from. Register it with
+/// [`super::DefaultRenderPipeline::add_render_target`], the pipeline allocates it before the first
+/// frame and reallocates it whenever its size changes, after which every pass is notified through
+/// [`super::RenderPass::target_resized`].
+///
+/// The bind group exposes the texture at binding 0 and a sampler at binding 1
+pub struct RenderTarget {
+    name: String,
+    size: TargetSize,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+    resources: Option<TargetResources>,
+}
+
+struct TargetResources {
+    size: PhysicalSize<u32>,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl RenderTarget {
+    /// The name is used as the label of the texture (visible in error messages and renderdoc)
+    pub fn new(name: &str, size: TargetSize, format: wgpu::TextureFormat) -> Self {
+        RenderTarget {
+            name: name.to_string(),
+            size,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            resources: None,
+        }
+    }
+
+    /// Adds usages to the texture, it is always usable as a render attachment and for sampling
+    pub fn with_usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage |= usage;
+        self
+    }
+
+    /// Changes the size of the target, it is reallocated before the next frame
+    pub fn set_size(&mut self, size: TargetSize) {
+        self.size = size;
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// Returns the current size in pixels, `None` before the target has been allocated
+    pub fn get_size(&self) -> Option<PhysicalSize<u32>> {
+        self.resources.as_ref().map(|resources| resources.size)
+    }
+
+    /// Returns true once the target has been allocated by the pipeline
+    pub fn is_allocated(&self) -> bool {
+        self.resources.is_some()
+    }
+
+    pub fn get_view(&self) -> &wgpu::TextureView {
+        &self.get_resources().view
+    }
+
+    pub fn get_sampler(&self) -> &wgpu::Sampler {
+        &self.get_resources().sampler
+    }
+
+    /// The layout stays the same when the target is reallocated, pipelines using it don't have to
+    /// be recreated
+    pub fn get_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.get_resources().bind_group_layout
+    }
+
+    /// Returns a bind group to sample the target with. A new bind group is created whenever the
+    /// target is reallocated
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup {
+        &self.get_resources().bind_group
+    }
+
+    fn get_resources(&self) -> &TargetResources {
+        self.resources.as_ref().unwrap_or_else(|| {
+            panic!(
+                "RenderTarget '{}' used before it was allocated by the pipeline",
+                self.name
+            )
+        })
+    }
+
+    /// Reallocates the texture if the window size requires it. Returns true if it was reallocated
+    pub(crate) fn update(&mut self, device: &wgpu::Device, window_size: PhysicalSize<u32>) -> bool {
+        let size = self.size.resolve(window_size);
+        if self.get_size() == Some(size) {
+            return false;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&self.name),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: self.usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let (sampler, bind_group_layout) = match self.resources.take() {
+            Some(resources) => (resources.sampler, resources.bind_group_layout),
+            None => self.create_sampler(device),
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&self.name),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        self.resources = Some(TargetResources {
+            size,
+            view,
+            sampler,
+            bind_group_layout,
+            bind_group,
+        });
+        true
+    }
+
+    fn create_sampler(&self, device: &wgpu::Device) -> (wgpu::Sampler, wgpu::BindGroupLayout) {
+        let sample_type = self
+            .format
+            .sample_type(None, Some(device.features()))
+            .unwrap_or(wgpu::TextureSampleType::Float { filterable: false });
+        let filterable = matches!(
+            sample_type,
+            wgpu::TextureSampleType::Float { filterable: true }
+        );
+        let filter = if filterable {
+            wgpu::FilterMode::Linear
+        } else {
+            wgpu::FilterMode::Nearest
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&self.name),
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&self.name),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(if filterable {
+                        wgpu::SamplerBindingType::Filtering
+                    } else {
+                        wgpu::SamplerBindingType::NonFiltering
+                    }),
+                    count: None,
+                },
+            ],
+        });
+        (sampler, bind_group_layout)
+    }
+
+    /// Drops the GPU resources, for example after the device was lost. The target is allocated
+    /// again before the next frame
+    pub(crate) fn release(&mut self) {
+        self.resources = None;
+    }
+}
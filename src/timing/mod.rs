@@ -0,0 +1,270 @@
+//! Frame timing utilities
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use winit::dpi::PhysicalSize;
+
+/// Information about the frame that is currently being rendered
+#[derive(Debug, Clone, Copy)]
+pub struct FrameInfo {
+    /// Time elapsed since the previous frame. Zero for the first frame
+    pub delta: Duration,
+    /// Time elapsed since the first frame
+    pub elapsed: Duration,
+    /// Index of the frame, starts at 0 and increases by one every frame
+    pub frame: u64,
+    /// Size of the render target in pixels
+    pub size: PhysicalSize<u32>,
+    /// How far between the previous and the next fixed update this frame is, in the range
+    /// `0.0..1.0`. Use this to interpolate state updated in
+    /// [`EventHandler::fixed_update`](crate::events::EventHandler::fixed_update). Always zero if
+    /// fixed updates are disabled
+    pub alpha: f32,
+}
+
+impl FrameInfo {
+    /// Returns the delta time in seconds
+    pub fn delta_secs(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+}
+
+/// Keeps track of frame timings for a single render target
+pub(crate) struct FrameClock {
+    first_frame: Option<Instant>,
+    last_frame: Instant,
+    frame: u64,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        FrameClock {
+            first_frame: None,
+            last_frame: Instant::now(),
+            frame: 0,
+        }
+    }
+
+    /// Starts a new frame
+    pub fn tick(&mut self, size: PhysicalSize<u32>) -> FrameInfo {
+        let now = Instant::now();
+        let (delta, first_frame) = match self.first_frame {
+            Some(first_frame) => (now - self.last_frame, first_frame),
+            None => {
+                self.first_frame = Some(now);
+                (Duration::ZERO, now)
+            }
+        };
+        let info = FrameInfo {
+            delta,
+            elapsed: now - first_frame,
+            frame: self.frame,
+            size,
+            alpha: 0.0,
+        };
+        self.last_frame = now;
+        self.frame += 1;
+        info
+    }
+}
+
+/// Accumulates frame time and decides how many fixed updates to run each frame
+pub(crate) struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+    max_steps: u32,
+}
+
+impl FixedTimestep {
+    pub fn new(rate: u32, max_steps: u32) -> Self {
+        FixedTimestep {
+            step: Duration::from_secs_f64(1.0 / rate.max(1) as f64),
+            accumulator: Duration::ZERO,
+            max_steps,
+        }
+    }
+
+    /// Length of a single fixed update
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Adds the frame time to the accumulator and returns the number of fixed updates to run.
+    /// If more than `max_steps` updates would be needed the excess time is dropped, so a slow
+    /// frame can't cause an ever growing backlog of updates
+    pub fn advance(&mut self, delta: Duration) -> u32 {
+        self.accumulator += delta;
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        if self.accumulator >= self.step {
+            self.accumulator =
+                Duration::from_nanos((self.accumulator.as_nanos() % self.step.as_nanos()) as u64);
+        }
+        steps
+    }
+
+    /// Interpolation factor between the previous and the next fixed update
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f64() / self.step.as_secs_f64()) as f32
+    }
+}
+
+/// How long before a paced frame is due the event loop stops sleeping and starts spinning. OS
+/// timers are too imprecise to hit the deadline by sleeping alone
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+/// Number of frames the [`FrameStats`] are calculated over
+const STATS_FRAMES: usize = 120;
+
+/// Frame time statistics of a window, calculated over the last 120 frames
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    pub average_frame_time: Duration,
+    pub min_frame_time: Duration,
+    pub max_frame_time: Duration,
+    /// Number of frames the statistics were calculated from
+    pub frames: usize,
+}
+
+impl FrameStats {
+    /// Returns the average framerate
+    pub fn fps(&self) -> f64 {
+        if self.average_frame_time.is_zero() {
+            return 0.0;
+        }
+        1.0 / self.average_frame_time.as_secs_f64()
+    }
+}
+
+/// Schedules frames of a single window when the framerate is limited, and keeps track of the
+/// achieved frame times
+pub(crate) struct FramePacer {
+    last_deadline: Option<Instant>,
+    next_frame: Option<Instant>,
+    frame_times: VecDeque<Duration>,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        FramePacer {
+            last_deadline: None,
+            next_frame: None,
+            frame_times: VecDeque::with_capacity(STATS_FRAMES),
+        }
+    }
+
+    /// Schedules the next frame `interval` after the previous one. Deadlines are anchored to the
+    /// previous deadline rather than the time the frame finished, so the framerate doesn't drift
+    pub fn schedule(&mut self, interval: Duration) {
+        let now = Instant::now();
+        let next = match self.last_deadline {
+            Some(last) => (last + interval).max(now),
+            None => now + interval,
+        };
+        self.last_deadline = Some(next);
+        self.next_frame = Some(next);
+    }
+
+    /// Forgets the schedule, used when the framerate is no longer limited
+    pub fn reset(&mut self) {
+        self.last_deadline = None;
+        self.next_frame = None;
+    }
+
+    /// Time at which the event loop has to wake up for this window, if a frame is scheduled
+    pub fn wake_time(&self) -> Option<Instant> {
+        self.next_frame.map(|next| next - SPIN_MARGIN)
+    }
+
+    /// If the scheduled frame is due within the spin margin, spins until its deadline and returns
+    /// true
+    pub fn wait_if_due(&mut self) -> bool {
+        let Some(next) = self.next_frame else {
+            return false;
+        };
+        if Instant::now() + SPIN_MARGIN < next {
+            return false;
+        }
+        while Instant::now() < next {
+            std::hint::spin_loop();
+        }
+        self.next_frame = None;
+        true
+    }
+
+    pub fn record(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == STATS_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        let frames = self.frame_times.len();
+        if frames == 0 {
+            return FrameStats::default();
+        }
+        let total: Duration = self.frame_times.iter().sum();
+        FrameStats {
+            average_frame_time: total / frames as u32,
+            min_frame_time: *self.frame_times.iter().min().unwrap(),
+            max_frame_time: *self.frame_times.iter().max().unwrap(),
+            frames,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_multiples_run_every_update() {
+        let mut timestep = FixedTimestep::new(60, 5);
+        let step = timestep.step();
+        assert_eq!(timestep.advance(step * 3), 3);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(step), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn partial_steps_accumulate() {
+        let mut timestep = FixedTimestep::new(60, 5);
+        let step = timestep.step();
+        assert_eq!(timestep.advance(step / 2), 0);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-3);
+        assert_eq!(timestep.advance(step - step / 2), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn updates_are_clamped_to_max_steps() {
+        // The updates above the limit are dropped, but the partial step is kept
+        let mut timestep = FixedTimestep::new(60, 5);
+        let step = timestep.step();
+        assert_eq!(timestep.advance(step * 8 + step / 4), 5);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-3);
+        assert_eq!(timestep.advance(step), 1);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn alpha_stays_below_one() {
+        let mut timestep = FixedTimestep::new(60, 3);
+        for millis in [1, 7, 16, 17, 33, 50, 100, 250, 1000] {
+            timestep.advance(Duration::from_millis(millis));
+            let alpha = timestep.alpha();
+            assert!(
+                (0.0..1.0).contains(&alpha),
+                "alpha {} after {}ms",
+                alpha,
+                millis
+            );
+        }
+    }
+}
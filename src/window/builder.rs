@@ -0,0 +1,164 @@
+use std::sync::{Arc, Mutex};
+
+use winit::{
+    dpi::{LogicalSize, Position, Size},
+    window::{Fullscreen, Icon, WindowAttributes},
+};
+
+use super::{Window, WindowManager, WindowRenderSettings};
+use crate::{events::EventHandler, rendering::RenderPipeline, EventLoop};
+
+/// Configures a [`Window`] before it is created. All attributes are applied when the underlying
+/// winit window is created
+#[derive(Debug, Clone)]
+pub struct WindowBuilder {
+    pub(crate) attributes: WindowAttributes,
+    pub(crate) cursor_visible: bool,
+    pub(crate) render_settings: WindowRenderSettings,
+}
+
+impl Default for WindowBuilder {
+    fn default() -> Self {
+        Self {
+            attributes: WindowAttributes::default()
+                .with_title("Taika Window")
+                .with_min_inner_size(LogicalSize::new(20.0, 20.0)),
+            cursor_visible: true,
+            render_settings: WindowRenderSettings::default(),
+        }
+    }
+}
+
+impl WindowBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the title of the window
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.attributes.title = title.to_string();
+        self
+    }
+
+    /// Sets the initial size of the window, excluding decorations
+    pub fn with_inner_size(mut self, size: impl Into<Size>) -> Self {
+        self.attributes.inner_size = Some(size.into());
+        self
+    }
+
+    /// Sets the minimum size of the window, excluding decorations
+    pub fn with_min_inner_size(mut self, size: impl Into<Size>) -> Self {
+        self.attributes.min_inner_size = Some(size.into());
+        self
+    }
+
+    /// Sets the maximum size of the window, excluding decorations
+    pub fn with_max_inner_size(mut self, size: impl Into<Size>) -> Self {
+        self.attributes.max_inner_size = Some(size.into());
+        self
+    }
+
+    /// Sets the initial position of the window
+    pub fn with_position(mut self, position: impl Into<Position>) -> Self {
+        self.attributes.position = Some(position.into());
+        self
+    }
+
+    /// Sets whether the window can be resized by the user
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.attributes.resizable = resizable;
+        self
+    }
+
+    /// Sets whether the window has decorations (title bar, borders)
+    pub fn with_decorations(mut self, decorations: bool) -> Self {
+        self.attributes.decorations = decorations;
+        self
+    }
+
+    /// Sets whether the window background is transparent
+    pub fn with_transparent(mut self, transparent: bool) -> Self {
+        self.attributes.transparent = transparent;
+        self
+    }
+
+    /// Sets whether the window starts maximized
+    pub fn with_maximized(mut self, maximized: bool) -> Self {
+        self.attributes.maximized = maximized;
+        self
+    }
+
+    /// Sets whether the window starts in fullscreen
+    pub fn with_fullscreen(mut self, fullscreen: Option<Fullscreen>) -> Self {
+        self.attributes.fullscreen = fullscreen;
+        self
+    }
+
+    /// Sets the icon of the window
+    pub fn with_window_icon(mut self, icon: Option<Icon>) -> Self {
+        self.attributes.window_icon = icon;
+        self
+    }
+
+    /// Sets whether the cursor is visible over the window
+    pub fn with_cursor_visible(mut self, visible: bool) -> Self {
+        self.cursor_visible = visible;
+        self
+    }
+
+    /// Overrides the presentation settings of the event loop for this window
+    pub fn with_render_settings(mut self, render_settings: WindowRenderSettings) -> Self {
+        self.render_settings = render_settings;
+        self
+    }
+
+    /// Overrides [`crate::RenderSettings::present_modes`] for this window, same as setting
+    /// [`WindowRenderSettings::present_modes`]
+    pub fn with_present_modes(mut self, present_modes: Vec<wgpu::PresentMode>) -> Self {
+        self.render_settings.present_modes = Some(present_modes);
+        self
+    }
+
+    /// Overrides [`crate::RenderSettings::surface_formats`] for this window, same as setting
+    /// [`WindowRenderSettings::surface_formats`]
+    pub fn with_surface_formats(mut self, surface_formats: Vec<wgpu::TextureFormat>) -> Self {
+        self.render_settings.surface_formats = Some(surface_formats);
+        self
+    }
+
+    /// Overrides [`crate::RenderSettings::alpha_modes`] for this window, same as setting
+    /// [`WindowRenderSettings::alpha_modes`]
+    pub fn with_alpha_modes(mut self, alpha_modes: Vec<wgpu::CompositeAlphaMode>) -> Self {
+        self.render_settings.alpha_modes = Some(alpha_modes);
+        self
+    }
+
+    /// Replaces all winit attributes, for settings that aren't exposed by the builder
+    pub fn with_attributes(mut self, attributes: WindowAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Creates the window, it will be opened once the event loop starts
+    pub fn build<'a, T: Send + 'static>(
+        self,
+        event_loop: &mut EventLoop<'a, T>,
+        pipeline: Arc<Mutex<dyn RenderPipeline>>,
+        event_handler: Box<dyn EventHandler<T>>,
+    ) -> Arc<Mutex<Window<'a, T>>> {
+        #[allow(clippy::arc_with_non_send_sync)]
+        let window = Arc::new(Mutex::new(Window::create(self, pipeline, event_handler)));
+        event_loop.windows.push(window.clone());
+        window
+    }
+
+    /// Creates the window on a running event loop
+    pub fn build_with_manager<'a, T: 'static>(
+        self,
+        window_manager: &WindowManager<'a, T>,
+        pipeline: Arc<Mutex<dyn RenderPipeline>>,
+        event_handler: Box<dyn EventHandler<T>>,
+    ) -> Arc<Mutex<Window<'a, T>>> {
+        window_manager.create_window_with_builder(self, pipeline, event_handler)
+    }
+}
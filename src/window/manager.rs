@@ -0,0 +1,75 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use winit::event_loop::EventLoopProxy;
+
+use super::{Window, WindowBuilder};
+use crate::{events::EventHandler, rendering::RenderPipeline, TaikaEvent};
+
+/// Windows waiting to be created or destroyed by the event loop
+pub(crate) struct PendingWindows<'a, T: 'static> {
+    pub created: Vec<Arc<Mutex<Window<'a, T>>>>,
+    pub destroyed: Vec<Arc<Mutex<Window<'a, T>>>>,
+}
+
+/// Creates and destroys windows while the event loop is running, for example from inside an
+/// [`EventHandler`] callback. Windows are created and destroyed by the event loop as soon as the
+/// current callback returns.
+///
+/// Unlike [`crate::LoopHandle`] this can only be used on the main thread.
+pub struct WindowManager<'a, T: 'static = ()> {
+    pending: Rc<RefCell<PendingWindows<'a, T>>>,
+    proxy: EventLoopProxy<TaikaEvent<T>>,
+}
+
+impl<T: 'static> Clone for WindowManager<'_, T> {
+    fn clone(&self) -> Self {
+        WindowManager {
+            pending: self.pending.clone(),
+            proxy: self.proxy.clone(),
+        }
+    }
+}
+
+impl<'a, T: 'static> WindowManager<'a, T> {
+    pub(crate) fn new(
+        pending: Rc<RefCell<PendingWindows<'a, T>>>,
+        proxy: EventLoopProxy<TaikaEvent<T>>,
+    ) -> Self {
+        WindowManager { pending, proxy }
+    }
+
+    /// Creates a new window. The window's surface is initialized against the shared device and
+    /// [`EventHandler::device_init`] is called once the event loop gets to it
+    pub fn create_window(
+        &self,
+        pipeline: Arc<Mutex<dyn RenderPipeline>>,
+        event_handler: Box<dyn EventHandler<T>>,
+    ) -> Arc<Mutex<Window<'a, T>>> {
+        self.create_window_with_builder(WindowBuilder::default(), pipeline, event_handler)
+    }
+
+    /// Creates a new window with the attributes of a [`WindowBuilder`]
+    pub fn create_window_with_builder(
+        &self,
+        builder: WindowBuilder,
+        pipeline: Arc<Mutex<dyn RenderPipeline>>,
+        event_handler: Box<dyn EventHandler<T>>,
+    ) -> Arc<Mutex<Window<'a, T>>> {
+        #[allow(clippy::arc_with_non_send_sync)]
+        let window = Arc::new(Mutex::new(Window::create(builder, pipeline, event_handler)));
+        self.pending.borrow_mut().created.push(window.clone());
+        let _ = self.proxy.send_event(TaikaEvent::WindowsChanged);
+        window
+    }
+
+    /// Closes and destroys a window. [`EventHandler::window_close`] is called before the window
+    /// is destroyed
+    pub fn destroy_window(&self, window: &Arc<Mutex<Window<'a, T>>>) {
+        self.pending.borrow_mut().destroyed.push(window.clone());
+        let _ = self.proxy.send_event(TaikaEvent::WindowsChanged);
+    }
+}
@@ -1,35 +1,66 @@
-use crate::{events::EventHandler, rendering::RenderPipeline, EventLoop, RenderSettings};
+use crate::{
+    events::{CloseDecision, EventHandler, SurfaceRecovery},
+    gpu,
+    rendering::{RenderPipeline, ResizeInfo},
+    timing::{FixedTimestep, FrameClock, FrameInfo, FramePacer, FrameStats},
+    DeviceCapabilities, EventLoop, RenderSettings, TaikaError,
+};
 use std::{
     sync::{Arc, Mutex},
-    time::Instant,
+    time::Duration,
 };
 use winit::dpi::PhysicalSize;
 
+mod builder;
+mod manager;
+pub use builder::WindowBuilder;
+pub(crate) use manager::PendingWindows;
+pub use manager::WindowManager;
+
 /// Represents a window
-pub struct Window<'a> {
+pub struct Window<'a, T: 'static = ()> {
     instance: Option<WindowInstance<'a>>,
     surface_config: Option<wgpu::SurfaceConfiguration>,
     render_pipeline: Arc<Mutex<dyn RenderPipeline>>,
-    event_handler: Box<dyn EventHandler>,
+    event_handler: Box<dyn EventHandler<T>>,
     target_properties: TargetProperties,
-    pub(crate) title: String,
-    pub(crate) cursor_visible: bool,
-    pub(crate) last_frame: Instant,
+    pub(crate) attributes: winit::window::WindowAttributes,
+    cursor_visible: bool,
+    frame_clock: FrameClock,
+    pub(crate) frame_pacer: FramePacer,
+    fixed_timestep: Option<FixedTimestep>,
+    render_settings: WindowRenderSettings,
+    /// Set when the surface has to be configured again before the next frame
+    pub(crate) surface_outdated: bool,
 }
 
 pub struct WindowInstance<'a> {
     pub handle: Arc<winit::window::Window>,
-    pub surface: wgpu::Surface<'a>,
+    /// The surface of the window, `None` while the application is suspended
+    pub surface: Option<wgpu::Surface<'a>>,
 }
 
-impl Window<'_> {
-    /// Creates a new window, the title can be set by calling [`Window::set_title`]
-    pub fn new<'a>(
-        event_loop: &mut EventLoop<'a>,
+impl<'a, T: 'static> Window<'a, T> {
+    /// Creates a new window, the title can be set by calling [`Window::set_title`]. Use a
+    /// [`WindowBuilder`] to configure the window before it is created. To create windows after
+    /// the event loop has started use a [`WindowManager`]
+    pub fn new(
+        event_loop: &mut EventLoop<'a, T>,
+        pipeline: Arc<Mutex<dyn RenderPipeline>>,
+        event_handler: Box<dyn EventHandler<T>>,
+    ) -> Arc<Mutex<Window<'a, T>>>
+    where
+        T: Send,
+    {
+        WindowBuilder::default().build(event_loop, pipeline, event_handler)
+    }
+
+    pub(crate) fn create(
+        builder: WindowBuilder,
         pipeline: Arc<Mutex<dyn RenderPipeline>>,
-        event_handler: Box<dyn EventHandler>,
-    ) -> Arc<Mutex<Window<'a>>> {
-        let window = Window {
+        event_handler: Box<dyn EventHandler<T>>,
+    ) -> Window<'a, T> {
+        Window {
             instance: None,
             surface_config: None,
             render_pipeline: pipeline,
@@ -37,86 +68,183 @@ impl Window<'_> {
             target_properties: TargetProperties {
                 format: wgpu::TextureFormat::Rgba8UnormSrgb, // Temporary values will be replaced
                 view_format: wgpu::TextureFormat::Rgba8Unorm, // later in runtime
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                sample_count: 1,
+                depth_format: None,
+                size: PhysicalSize::new(1, 1),
             },
-            title: "Taika Window".to_string(),
-            cursor_visible: true,
-            last_frame: Instant::now(),
-        };
-        let window = Arc::new(Mutex::new(window));
-        event_loop.windows.push(window.clone());
-        window
+            attributes: builder.attributes,
+            cursor_visible: builder.cursor_visible,
+            frame_clock: FrameClock::new(),
+            frame_pacer: FramePacer::new(),
+            fixed_timestep: None,
+            render_settings: builder.render_settings,
+            surface_outdated: false,
+        }
     }
 
     pub(crate) fn init(
         &mut self,
         instance: &wgpu::Instance,
         window: winit::window::Window,
-    ) -> Result<(), wgpu::CreateSurfaceError> {
+    ) -> Result<(), TaikaError> {
         let window = Arc::new(window);
         let surface = instance.create_surface(window.clone())?;
         self.instance = Some(WindowInstance {
             handle: window,
-            surface,
+            surface: Some(surface),
         });
         self.set_cursor_visible(self.cursor_visible);
         Ok(())
     }
 
+    pub(crate) fn is_initialized(&self) -> bool {
+        self.instance.is_some()
+    }
+
+    /// Drops the surface and the winit window
+    pub(crate) fn destroy(&mut self) {
+        self.surface_config = None;
+        self.instance = None;
+    }
+
     pub(crate) fn pre_present_notify(&self) {
         self.instance.as_ref().unwrap().handle.pre_present_notify();
     }
 
-    pub(crate) fn request_redraw(&self) {
-        self.instance.as_ref().unwrap().handle.request_redraw();
+    /// Requests a new frame to be rendered. Mainly useful with [`crate::RedrawMode::OnDemand`].
+    ///
+    /// Event handler callbacks are called while the window is locked, use
+    /// [`crate::LoopHandle::request_redraw`] from inside them instead
+    pub fn request_redraw(&self) {
+        if let Some(instance) = &self.instance {
+            instance.handle.request_redraw();
+        }
     }
 
+    /// Configures the surface with the current render settings. Fails if the adapter can't
+    /// present to the surface
     pub(crate) fn configure_surface(
         &mut self,
         adapter: &wgpu::Adapter,
         device: &wgpu::Device,
         render_settings: &RenderSettings,
-    ) {
+    ) -> Result<(), TaikaError> {
         let size = self.instance.as_ref().unwrap().handle.inner_size();
         let size: PhysicalSize<u32> = (size.width.max(1), size.height.max(1)).into();
 
-        let swapchain_capabilities = self
-            .instance
-            .as_ref()
-            .unwrap()
-            .surface
-            .get_capabilities(adapter);
-        let mut swapchain_format = swapchain_capabilities.formats[0];
-
-        #[cfg(not(target_arch = "wasm32"))]
+        let swapchain_capabilities = self.get_surface().get_capabilities(adapter);
+        if swapchain_capabilities.formats.is_empty()
+            || swapchain_capabilities.alpha_modes.is_empty()
         {
-            swapchain_format = swapchain_format.add_srgb_suffix();
-            self.target_properties.format = swapchain_format.remove_srgb_suffix();
+            return Err(TaikaError::SurfaceUnsupported {
+                adapter: adapter.get_info().name,
+            });
         }
-        self.target_properties.view_format = swapchain_format;
-        #[cfg(target_arch = "wasm32")]
-        {
-            self.target_properties.format = swapchain_format;
-        }
-        let mut present_mode = wgpu::PresentMode::AutoNoVsync;
-        if render_settings.vsync {
-            present_mode = wgpu::PresentMode::AutoVsync;
+        let (format, view_format) = select_surface_format(
+            self.render_settings
+                .surface_formats
+                .as_ref()
+                .unwrap_or(&render_settings.surface_formats),
+            &swapchain_capabilities.formats,
+        );
+        let alpha_mode = select_alpha_mode(
+            self.render_settings
+                .alpha_modes
+                .as_ref()
+                .unwrap_or(&render_settings.alpha_modes),
+            self.attributes.transparent,
+            &swapchain_capabilities.alpha_modes,
+        );
+        let sample_count = gpu::select_sample_count(
+            Some(adapter),
+            device,
+            view_format,
+            self.render_settings
+                .sample_count
+                .unwrap_or(render_settings.sample_count),
+        );
+        let target_properties = TargetProperties {
+            format,
+            view_format,
+            alpha_mode,
+            sample_count,
+            depth_format: None,
+            size,
+        };
+        // Pipelines are built for a specific format, so they have to be initialized again if it
+        // changed since the last time the surface was configured. A different size alone doesn't
+        // require that
+        let mut previous = self.target_properties.clone();
+        previous.size = size;
+        if self.surface_config.is_some() && target_properties != previous {
+            self.render_pipeline.lock().unwrap().reset();
         }
+        self.target_properties = target_properties;
+        let present_mode = select_present_mode(
+            self.render_settings
+                .present_modes
+                .as_ref()
+                .unwrap_or(&render_settings.present_modes),
+            self.render_settings.vsync.unwrap_or(render_settings.vsync),
+            &swapchain_capabilities.present_modes,
+        );
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: self.target_properties.format,
             width: size.width,
             height: size.height,
             present_mode,
-            alpha_mode: swapchain_capabilities.alpha_modes[0],
+            alpha_mode,
             view_formats: vec![self.target_properties.view_format],
-            desired_maximum_frame_latency: 1,
+            desired_maximum_frame_latency: self
+                .render_settings
+                .desired_maximum_frame_latency
+                .unwrap_or(render_settings.desired_maximum_frame_latency),
         };
-        self.instance
-            .as_ref()
-            .unwrap()
-            .surface
-            .configure(device, &config);
+        self.get_surface().configure(device, &config);
         self.surface_config = Some(config);
+        self.surface_outdated = false;
+        Ok(())
+    }
+
+    /// Returns the present mode the surface is configured with, `None` if the window doesn't
+    /// have a surface yet
+    pub fn get_present_mode(&self) -> Option<wgpu::PresentMode> {
+        self.surface_config
+            .as_ref()
+            .map(|config| config.present_mode)
+    }
+
+    /// Replaces the render settings this window overrides. Applied before the next frame
+    pub fn set_render_settings(&mut self, render_settings: WindowRenderSettings) {
+        self.render_settings = render_settings;
+        self.surface_outdated = true;
+        self.frame_pacer.reset();
+    }
+
+    /// Returns the render settings this window overrides
+    pub fn get_render_settings(&self) -> &WindowRenderSettings {
+        &self.render_settings
+    }
+
+    /// Returns the minimum time between frames of this window if the framerate is limited.
+    /// Present modes that wait for vsync are never limited
+    pub(crate) fn frame_interval(&self, render_settings: &RenderSettings) -> Option<Duration> {
+        let vsync = matches!(
+            self.get_present_mode()?,
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed
+        );
+        match self
+            .render_settings
+            .max_framerate
+            .or(render_settings.max_framerate)
+        {
+            Some(max_framerate) if !vsync && max_framerate > 0 => {
+                Some(Duration::from_secs_f64(1.0 / max_framerate as f64))
+            }
+            _ => None,
+        }
     }
 
     pub(crate) fn resize_surface(
@@ -126,9 +254,10 @@ impl Window<'_> {
         queue: &wgpu::Queue,
     ) {
         let config = self.surface_config.as_ref().unwrap().clone();
+        let size = PhysicalSize::new(size.width.max(1), size.height.max(1));
         self.surface_config = Some(wgpu::SurfaceConfiguration {
-            width: size.width.max(1),
-            height: size.height.max(1),
+            width: size.width,
+            height: size.height,
             format: config.format,
             present_mode: config.present_mode,
             alpha_mode: config.alpha_mode,
@@ -136,13 +265,65 @@ impl Window<'_> {
             desired_maximum_frame_latency: config.desired_maximum_frame_latency,
             view_formats: config.view_formats.clone(),
         });
-        self.instance
-            .as_ref()
-            .unwrap()
-            .surface
+        self.get_surface()
             .configure(device, self.surface_config.as_ref().unwrap());
+        self.target_properties.size = size;
+        let resize_info = ResizeInfo {
+            old_size: PhysicalSize::new(config.width, config.height),
+            new_size: size,
+            scale_factor: self.instance.as_ref().unwrap().handle.scale_factor(),
+        };
+        self.render_pipeline
+            .lock()
+            .unwrap()
+            .resize(device, queue, &resize_info);
         self.event_handler
-            .window_resize(size.width.max(1), size.height.max(1), device, queue)
+            .window_resize(size.width, size.height, device, queue)
+    }
+
+    /// Reconfigures the surface after it has been lost or become outdated, picking up any size
+    /// change the window went through
+    pub(crate) fn reconfigure_surface(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let size = self.instance.as_ref().unwrap().handle.inner_size();
+        let config = self.surface_config.as_ref().unwrap();
+        if config.width != size.width.max(1) || config.height != size.height.max(1) {
+            self.resize_surface(device, size, queue);
+        } else {
+            self.get_surface().configure(device, config);
+        }
+    }
+
+    /// Acquires the next frame of the surface. Lost and outdated surfaces are reconfigured and
+    /// the frame is acquired again, other errors are reported to the [`EventHandler`]
+    pub(crate) fn acquire_frame(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Option<wgpu::SurfaceTexture>, wgpu::SurfaceError> {
+        let error = match self.get_surface().get_current_texture() {
+            Ok(frame) => return Ok(Some(frame)),
+            Err(error) => error,
+        };
+        let (result, recovery) = match error {
+            wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated => {
+                self.reconfigure_surface(device, queue);
+                match self.get_surface().get_current_texture() {
+                    Ok(frame) => (Ok(Some(frame)), SurfaceRecovery::Reconfigured),
+                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                        (Err(wgpu::SurfaceError::OutOfMemory), SurfaceRecovery::Fatal)
+                    }
+                    Err(_) => (Ok(None), SurfaceRecovery::Skipped),
+                }
+            }
+            wgpu::SurfaceError::OutOfMemory => {
+                (Err(wgpu::SurfaceError::OutOfMemory), SurfaceRecovery::Fatal)
+            }
+            wgpu::SurfaceError::Timeout | wgpu::SurfaceError::Other => {
+                (Ok(None), SurfaceRecovery::Skipped)
+            }
+        };
+        self.event_handler.surface_error(&error, recovery);
+        result
     }
 
     /// Returns the underlying winit window handle
@@ -155,72 +336,163 @@ impl Window<'_> {
         self.instance.as_ref().unwrap().handle.id()
     }
 
-    pub(crate) fn get_surface(&self) -> &wgpu::Surface {
-        &self.instance.as_ref().unwrap().surface
+    pub(crate) fn get_surface(&self) -> &wgpu::Surface<'a> {
+        self.instance.as_ref().unwrap().surface.as_ref().unwrap()
+    }
+
+    /// Returns true if the window has a surface that can be rendered to
+    pub(crate) fn has_surface(&self) -> bool {
+        self.instance
+            .as_ref()
+            .is_some_and(|instance| instance.surface.is_some())
+    }
+
+    /// Creates a new surface for the window, after the application has been resumed
+    pub(crate) fn create_surface(&mut self, instance: &wgpu::Instance) -> Result<(), TaikaError> {
+        let window_instance = self.instance.as_mut().unwrap();
+        window_instance.surface = Some(instance.create_surface(window_instance.handle.clone())?);
+        Ok(())
+    }
+
+    /// Drops the surface of the window, called when the application is suspended
+    pub(crate) fn drop_surface(&mut self) {
+        if let Some(instance) = self.instance.as_mut() {
+            instance.surface = None;
+        }
+    }
+
+    pub(crate) fn do_suspended(&mut self) {
+        self.event_handler.suspended();
+    }
+
+    pub(crate) fn do_resumed(&mut self) {
+        self.event_handler.resumed();
     }
 
     /// Sets the title of the window
     pub fn set_title(&mut self, title: &str) {
-        if self.instance.is_none() {
-            self.title = title.to_string();
+        if let Some(instance) = &self.instance {
+            instance.handle.set_title(title);
         } else {
-            self.instance.as_ref().unwrap().handle.set_title(title);
+            self.attributes.title = title.to_string();
         }
     }
 
     /// Resize the window. This might fail silently
     pub fn set_size(&mut self, size: winit::dpi::PhysicalSize<u32>) {
-        // for now we dont care about the result
-        let _ = self
-            .instance
-            .as_ref()
-            .unwrap()
-            .handle
-            .request_inner_size(size);
+        if let Some(instance) = &self.instance {
+            // for now we dont care about the result
+            let _ = instance.handle.request_inner_size(size);
+        } else {
+            self.attributes.inner_size = Some(size.into());
+        }
     }
 
     /// Sets the position of the window
     pub fn set_position(&mut self, position: winit::dpi::PhysicalPosition<i32>) {
-        self.instance
-            .as_ref()
-            .unwrap()
-            .handle
-            .set_outer_position(position);
+        if let Some(instance) = &self.instance {
+            instance.handle.set_outer_position(position);
+        } else {
+            self.attributes.position = Some(position.into());
+        }
     }
 
     /// Sets the window to fullscreen
     pub fn set_fullscreen(&mut self, fullscreen: Option<winit::window::Fullscreen>) {
-        self.instance
-            .as_ref()
-            .unwrap()
-            .handle
-            .set_fullscreen(fullscreen);
+        if let Some(instance) = &self.instance {
+            instance.handle.set_fullscreen(fullscreen);
+        } else {
+            self.attributes.fullscreen = fullscreen;
+        }
+    }
+
+    /// Sets whether the window can be resized by the user
+    pub fn set_resizable(&mut self, resizable: bool) {
+        if let Some(instance) = &self.instance {
+            instance.handle.set_resizable(resizable);
+        } else {
+            self.attributes.resizable = resizable;
+        }
+    }
+
+    /// Sets whether the window has decorations (title bar, borders)
+    pub fn set_decorations(&mut self, decorations: bool) {
+        if let Some(instance) = &self.instance {
+            instance.handle.set_decorations(decorations);
+        } else {
+            self.attributes.decorations = decorations;
+        }
+    }
+
+    /// Maximizes or unmaximizes the window
+    pub fn set_maximized(&mut self, maximized: bool) {
+        if let Some(instance) = &self.instance {
+            instance.handle.set_maximized(maximized);
+        } else {
+            self.attributes.maximized = maximized;
+        }
+    }
+
+    /// Sets the icon of the window
+    pub fn set_window_icon(&mut self, icon: Option<winit::window::Icon>) {
+        if let Some(instance) = &self.instance {
+            instance.handle.set_window_icon(icon);
+        } else {
+            self.attributes.window_icon = icon;
+        }
     }
 
     /// Sets the visibility of the cursor
     pub fn set_cursor_visible(&mut self, visible: bool) {
-        if self.instance.is_none() {
-            self.cursor_visible = visible;
+        if let Some(instance) = &self.instance {
+            instance.handle.set_cursor_visible(visible);
         } else {
-            self.instance
-                .as_ref()
-                .unwrap()
-                .handle
-                .set_cursor_visible(visible);
+            self.cursor_visible = visible;
         }
     }
 
+    /// Returns frame time statistics over the last 120 frames
+    pub fn get_frame_stats(&self) -> FrameStats {
+        self.frame_pacer.stats()
+    }
+
     /// Returns the taika [`RenderPipeline`]
     pub fn get_render_pipeline(&self) -> Arc<Mutex<dyn RenderPipeline>> {
         self.render_pipeline.clone()
     }
 
-    pub(crate) fn do_frame(&mut self) {
-        self.event_handler.window_frame();
+    /// Creates the fixed timestep from the current render settings of the event loop. Called when
+    /// the window is initialized and whenever the fixed update settings change, which drops the
+    /// time accumulated towards the next update
+    pub(crate) fn reset_fixed_timestep(&mut self, render_settings: &RenderSettings) {
+        self.fixed_timestep = render_settings
+            .fixed_update_rate
+            .map(|rate| FixedTimestep::new(rate, render_settings.max_fixed_updates_per_frame));
     }
 
-    pub(crate) fn do_after_frame(&mut self) {
-        self.event_handler.window_after_frame();
+    /// Starts a new frame, running any pending fixed updates and returning the [`FrameInfo`] for
+    /// it
+    pub(crate) fn do_frame(&mut self) -> FrameInfo {
+        let size = match &self.surface_config {
+            Some(config) => PhysicalSize::new(config.width, config.height),
+            None => PhysicalSize::new(1, 1),
+        };
+        let mut frame_info = self.frame_clock.tick(size);
+        if frame_info.frame > 0 {
+            self.frame_pacer.record(frame_info.delta);
+        }
+        if let Some(fixed_timestep) = &mut self.fixed_timestep {
+            for _ in 0..fixed_timestep.advance(frame_info.delta) {
+                self.event_handler.fixed_update(fixed_timestep.step());
+            }
+            frame_info.alpha = fixed_timestep.alpha();
+        }
+        self.event_handler.window_frame(&frame_info);
+        frame_info
+    }
+
+    pub(crate) fn do_after_frame(&mut self, frame_info: &FrameInfo) {
+        self.event_handler.window_after_frame(frame_info);
     }
 
     pub(crate) fn do_focus(&mut self, focused: bool) {
@@ -231,8 +503,8 @@ impl Window<'_> {
         }
     }
 
-    pub(crate) fn do_closed(&mut self) {
-        self.event_handler.window_close();
+    pub(crate) fn do_closed(&mut self) -> CloseDecision {
+        self.event_handler.window_close()
     }
 
     pub(crate) fn do_device_init(
@@ -240,9 +512,24 @@ impl Window<'_> {
         adapter: &wgpu::Adapter,
         device: Arc<Mutex<wgpu::Device>>,
         queue: Arc<Mutex<wgpu::Queue>>,
+        capabilities: &DeviceCapabilities,
     ) {
-        self.event_handler
-            .device_init(adapter, device, queue, self.target_properties.clone());
+        self.event_handler.device_init(
+            adapter,
+            device,
+            queue,
+            self.target_properties.clone(),
+            capabilities.clone(),
+        );
+    }
+
+    pub(crate) fn do_device_lost(&mut self) {
+        self.event_handler.device_lost();
+        self.render_pipeline.lock().unwrap().reset();
+    }
+
+    pub(crate) fn do_user_event(&mut self, event: &T) {
+        self.event_handler.user_event(event);
     }
 
     pub(crate) fn do_window_event(&mut self, event: &winit::event::WindowEvent) {
@@ -255,9 +542,249 @@ impl Window<'_> {
     }
 }
 
+/// Picks the first preferred format supported by the surface, returning the format of the surface
+/// and the format it is viewed as. `supported` can't be empty
+fn select_surface_format(
+    preferred: &[wgpu::TextureFormat],
+    supported: &[wgpu::TextureFormat],
+) -> (wgpu::TextureFormat, wgpu::TextureFormat) {
+    for &format in preferred {
+        if supported.contains(&format) {
+            return (format, format);
+        }
+        // Webgpu doesn't allow views with a different format than the surface
+        #[cfg(not(target_arch = "wasm32"))]
+        if supported.contains(&format.remove_srgb_suffix()) {
+            return (format.remove_srgb_suffix(), format);
+        }
+    }
+    let format = supported[0];
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        (format.remove_srgb_suffix(), format.add_srgb_suffix())
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        (format, format)
+    }
+}
+
+/// Picks the first preferred alpha mode supported by the surface. Transparent windows fall back to
+/// a mode that blends with whatever is behind the window. `supported` can't be empty
+fn select_alpha_mode(
+    preferred: &[wgpu::CompositeAlphaMode],
+    transparent: bool,
+    supported: &[wgpu::CompositeAlphaMode],
+) -> wgpu::CompositeAlphaMode {
+    let transparent_modes: &[wgpu::CompositeAlphaMode] = if transparent {
+        &[
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+        ]
+    } else {
+        &[]
+    };
+    preferred
+        .iter()
+        .chain(transparent_modes)
+        .copied()
+        .find(|mode| *mode == wgpu::CompositeAlphaMode::Auto || supported.contains(mode))
+        .unwrap_or(supported[0])
+}
+
+/// Picks the first preferred present mode supported by the surface. The `Auto` modes are always
+/// supported, `Fifo` is used if none of the preferred modes are
+fn select_present_mode(
+    preferred: &[wgpu::PresentMode],
+    vsync: bool,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    if preferred.is_empty() {
+        return if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        };
+    }
+    preferred
+        .iter()
+        .copied()
+        .find(|mode| {
+            matches!(
+                mode,
+                wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+            ) || supported.contains(mode)
+        })
+        .unwrap_or(wgpu::PresentMode::Fifo)
+}
+
+/// Decides when closing a window exits the event loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CloseBehavior {
+    /// The event loop exits once every window has been closed
+    #[default]
+    ExitOnLastWindowClosed,
+    /// The event loop exits when the main window (the first window created) is closed
+    ExitOnMainWindowClosed,
+    /// The event loop exits when any window is closed
+    ExitOnAnyWindowClosed,
+}
+
+/// Presentation settings of a single window, overriding the [`RenderSettings`] of the event loop.
+/// Every setting left as `None` uses the value from the render settings
+#[derive(Debug, Clone, Default)]
+pub struct WindowRenderSettings {
+    /// Overrides [`RenderSettings::vsync`]
+    pub vsync: Option<bool>,
+    /// Overrides [`RenderSettings::present_modes`]
+    pub present_modes: Option<Vec<wgpu::PresentMode>>,
+    /// Overrides [`RenderSettings::desired_maximum_frame_latency`]
+    pub desired_maximum_frame_latency: Option<u32>,
+    /// Overrides [`RenderSettings::max_framerate`], `Some(0)` removes the limit for this window
+    pub max_framerate: Option<u16>,
+    /// Overrides [`RenderSettings::surface_formats`]
+    pub surface_formats: Option<Vec<wgpu::TextureFormat>>,
+    /// Overrides [`RenderSettings::alpha_modes`]
+    pub alpha_modes: Option<Vec<wgpu::CompositeAlphaMode>>,
+    /// Overrides [`RenderSettings::sample_count`]
+    pub sample_count: Option<u32>,
+}
+
 /// Info about the texture format used by the window
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TargetProperties {
     pub format: wgpu::TextureFormat,
     pub view_format: wgpu::TextureFormat,
+    /// How the window is composited with whatever is behind it. With `PreMultiplied` the colors
+    /// written to the target have to be multiplied by their alpha
+    pub alpha_mode: wgpu::CompositeAlphaMode,
+    /// Number of samples of the color attachments render passes draw to. Pipelines have to be
+    /// created with a matching `wgpu::MultisampleState::count`
+    pub sample_count: u32,
+    /// Format of the depth/stencil attachment, `None` if there is none. Windows don't have a depth
+    /// buffer themselves, render passes that manage one (like
+    /// [`crate::rendering::PrimaryDrawPass`]) set it for their drawables
+    pub depth_format: Option<wgpu::TextureFormat>,
+    /// Size of the target in pixels
+    pub size: PhysicalSize<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::{CompositeAlphaMode, PresentMode, TextureFormat};
+
+    #[test]
+    fn present_mode_follows_vsync_without_preferences() {
+        let supported = [PresentMode::Fifo, PresentMode::Immediate];
+        assert_eq!(
+            select_present_mode(&[], true, &supported),
+            PresentMode::AutoVsync
+        );
+        assert_eq!(
+            select_present_mode(&[], false, &supported),
+            PresentMode::AutoNoVsync
+        );
+    }
+
+    #[test]
+    fn first_supported_present_mode_is_picked() {
+        let supported = [PresentMode::Fifo, PresentMode::Immediate];
+        assert_eq!(
+            select_present_mode(
+                &[PresentMode::Mailbox, PresentMode::Immediate],
+                true,
+                &supported
+            ),
+            PresentMode::Immediate
+        );
+        assert_eq!(
+            select_present_mode(
+                &[PresentMode::Mailbox, PresentMode::AutoNoVsync],
+                true,
+                &supported
+            ),
+            PresentMode::AutoNoVsync
+        );
+    }
+
+    #[test]
+    fn present_mode_falls_back_to_fifo() {
+        assert_eq!(
+            select_present_mode(&[PresentMode::Mailbox], false, &[PresentMode::Fifo]),
+            PresentMode::Fifo
+        );
+    }
+
+    #[test]
+    fn preferred_surface_format_is_picked() {
+        let supported = [TextureFormat::Bgra8Unorm, TextureFormat::Rgba16Float];
+        assert_eq!(
+            select_surface_format(&[TextureFormat::Rgba16Float], &supported),
+            (TextureFormat::Rgba16Float, TextureFormat::Rgba16Float)
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn srgb_format_is_viewed_on_linear_surface() {
+        assert_eq!(
+            select_surface_format(
+                &[TextureFormat::Rgba8UnormSrgb],
+                &[TextureFormat::Bgra8Unorm, TextureFormat::Rgba8Unorm]
+            ),
+            (TextureFormat::Rgba8Unorm, TextureFormat::Rgba8UnormSrgb)
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn surface_format_falls_back_to_srgb_view_of_first_format() {
+        assert_eq!(
+            select_surface_format(
+                &[TextureFormat::Rgba16Float],
+                &[TextureFormat::Bgra8UnormSrgb, TextureFormat::Rgba8Unorm]
+            ),
+            (TextureFormat::Bgra8Unorm, TextureFormat::Bgra8UnormSrgb)
+        );
+    }
+
+    #[test]
+    fn preferred_alpha_mode_is_picked() {
+        let supported = [
+            CompositeAlphaMode::Opaque,
+            CompositeAlphaMode::PreMultiplied,
+        ];
+        assert_eq!(
+            select_alpha_mode(
+                &[
+                    CompositeAlphaMode::PostMultiplied,
+                    CompositeAlphaMode::Opaque
+                ],
+                true,
+                &supported
+            ),
+            CompositeAlphaMode::Opaque
+        );
+        assert_eq!(
+            select_alpha_mode(&[CompositeAlphaMode::Auto], false, &supported),
+            CompositeAlphaMode::Auto
+        );
+    }
+
+    #[test]
+    fn transparent_windows_prefer_blending_alpha_modes() {
+        let supported = [
+            CompositeAlphaMode::Opaque,
+            CompositeAlphaMode::PostMultiplied,
+        ];
+        assert_eq!(
+            select_alpha_mode(&[], true, &supported),
+            CompositeAlphaMode::PostMultiplied
+        );
+        assert_eq!(
+            select_alpha_mode(&[], false, &supported),
+            CompositeAlphaMode::Opaque
+        );
+    }
 }